@@ -1,21 +1,53 @@
-use crate::gen::{AllocGen, Gen};
-use crate::id::NonMaxU32;
-use crate::valid::Validator;
-use crate::{Dynamic, Entity, Id, IdRange, Static, Valid};
+use crate::entity::IdType;
+use crate::gen::{AllocGen, Generation};
+use crate::id::{GenType, NonMaxU32, RangeIter};
+use crate::join::{Join, Query};
+use crate::valid::{SyncError, ValidIter, Validator};
+use crate::{DynamicIdType, Entity, Id, IdRange, Static, Valid, ValidId};
 use iter_context::ContextualIterator;
 use ref_cast::RefCast;
+use std::collections::VecDeque;
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
 
 #[cfg(feature = "rayon")]
 use rayon::prelude::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
 
 /// Allocates indices for dynamic Ids.
+///
+/// Ids reserved with [`Allocator::reserve`] are not serialized until the allocator is flushed,
+/// so it must be flushed before serializing, or the reserved Ids would be issued again
+/// after deserializing. Debug builds panic if there are pending reservations.
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Allocator<E: Entity> {
-    entries: Vec<Entry>,
+    entries: Vec<Entry<GenType<E>>>,
     next_dead: Option<crate::id::NonMaxU32>,
+    last_dead: Option<NonMaxU32>,
     gen: AllocGen<E>,
+    free_list: FreeList,
+    retire_slots: bool,
+    retired: u32,
+    /// The generation of new slots, raised by [`Allocator::compact`] past the generations
+    /// of the slots it truncates so that their Ids are never reissued
+    first_gen: Option<GenType<E>>,
+    /// The number of times the allocator has been restored, which is mixed into `gen`
+    /// so that every restore gives a new `AllocGen<E>`, even from the same snapshot
+    restores: u32,
+    history: History<E>,
+    /// How far [`Allocator::reserve`] has advanced through the dead list, see [`Cursor`]
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_deserializing, skip_serializing_if = "skip_reserved_dead")
+    )]
+    reserved_dead: AtomicU64,
+    /// The number of new entries reserved past the end of `entries`
+    #[cfg_attr(
+        feature = "serde",
+        serde(skip_deserializing, skip_serializing_if = "skip_reserved_new")
+    )]
+    reserved_new: AtomicU32,
     marker: PhantomData<E>,
 }
 
@@ -25,7 +57,16 @@ impl<E: Entity> Default for Allocator<E> {
         Self {
             entries: Default::default(),
             next_dead: Default::default(),
+            last_dead: Default::default(),
             gen: Default::default(),
+            free_list: Default::default(),
+            retire_slots: Default::default(),
+            retired: Default::default(),
+            first_gen: Default::default(),
+            restores: Default::default(),
+            history: Default::default(),
+            reserved_dead: Default::default(),
+            reserved_new: Default::default(),
             marker: Default::default(),
         }
     }
@@ -35,30 +76,98 @@ impl<E: Entity> PartialEq for Allocator<E> {
     fn eq(&self, other: &Self) -> bool {
         self.entries.eq(&other.entries)
             && self.next_dead.eq(&other.next_dead)
+            && self.last_dead.eq(&other.last_dead)
             && self.gen.eq(&other.gen)
+            && self.free_list.eq(&other.free_list)
+            && self.retire_slots.eq(&other.retire_slots)
+            && self.retired.eq(&other.retired)
+            && self.first_gen.eq(&other.first_gen)
+            && self.restores.eq(&other.restores)
     }
 }
 
-impl<E: Entity<IdType = Dynamic>> Allocator<E> {
+impl<E: Entity> Allocator<E> {
+    /// Sets the order in which dead slots are reused. Only affects slots killed from now on.
+    #[inline]
+    pub fn with_free_list(mut self, free_list: FreeList) -> Self {
+        self.free_list = free_list;
+        self
+    }
+
+    /// Once the generation of a slot is exhausted, the slot is retired and never reused,
+    /// instead of wrapping around to the first generation where a stale Id could be mistaken
+    /// for a living one.
+    #[inline]
+    pub fn with_slot_retirement(mut self) -> Self {
+        self.retire_slots = true;
+        self
+    }
+
+    /// Keeps a record of the last `capacity` killed Ids,
+    /// so that collections that are out of sync can report which kills they missed
+    #[inline]
+    pub fn with_history(mut self, capacity: usize) -> Self {
+        self.history.capacity = capacity;
+        self
+    }
+
+    /// The number of slots that have been permanently retired
+    #[inline]
+    pub fn retired(&self) -> usize {
+        self.retired as usize
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> Allocator<E> {
     #[inline]
     pub fn create(&mut self) -> Valid<Id<E>> {
+        self.flush();
         let id = self.reuse_index().unwrap_or_else(|| self.create_new());
         Valid::new(id)
     }
 
+    /// Creates `count` Ids, filling dead slots before growing the allocator
+    pub fn create_many(&mut self, count: usize) -> Valid<'_, Vec<Id<E>>> {
+        self.flush();
+        let mut ids = Vec::with_capacity(count);
+
+        while ids.len() < count {
+            match self.reuse_index() {
+                Some(id) => ids.push(id),
+                None => break,
+            }
+        }
+
+        let remaining = count - ids.len();
+        self.entries.reserve(remaining);
+        ids.extend(std::iter::repeat_with(|| self.create_new()).take(remaining));
+
+        Valid::new(ids)
+    }
+
     fn create_new(&mut self) -> Id<E> {
-        let index = self.entries.len() as u32;
-        let id = Id::first(index);
+        let id = self.new_id(self.entries.len() as u32);
         self.entries.push(Entry::from(id));
         id
     }
 
+    #[inline]
+    fn new_id(&self, index: u32) -> Id<E> {
+        match self.first_gen {
+            Some(gen) => Id::new(index, gen),
+            None => Id::first(index),
+        }
+    }
+
     fn reuse_index(&mut self) -> Option<Id<E>> {
         let index = self.next_dead?.get();
         let entry = self.entries.get_mut(index as usize)?;
         match *entry {
             Entry::Dead { next_dead, gen } => {
                 self.next_dead = next_dead;
+                if next_dead.is_none() {
+                    self.last_dead = None;
+                }
                 let id = Id::new(index, gen);
                 *entry = Entry::from(id);
                 Some(id)
@@ -66,29 +175,131 @@ impl<E: Entity<IdType = Dynamic>> Allocator<E> {
             Entry::Alive { index: _, gen: _ } => {
                 panic!("Allocator::reuse_index, Entry::Alive found at dead index ")
             }
+            Entry::Retired => {
+                panic!("Allocator::reuse_index, Entry::Retired found at dead index ")
+            }
+        }
+    }
+
+    /// Reserves an Id through a shared reference, so that Ids can be reserved from many threads
+    /// at once. Dead slots are reserved before new ones, as with [`Allocator::create`].
+    ///
+    /// The Id is not alive until the allocator is flushed,
+    /// which happens implicitly before it creates or kills any Ids
+    #[track_caller]
+    pub fn reserve(&self) -> Id<E> {
+        let mut cursor = self.reserved_dead.load(Ordering::Relaxed);
+
+        // The dead list cannot change while the allocator is shared,
+        // so the cursor only ever moves forward along it
+        while let Some(index) = Cursor::decode(cursor, self.next_dead) {
+            let Entry::Dead { next_dead, gen } = self.entries[index.get() as usize] else {
+                panic!("Allocator::reserve, dead index does not point to Entry::Dead")
+            };
+
+            let next = Cursor::encode(next_dead);
+            match self.reserved_dead.compare_exchange_weak(
+                cursor,
+                next,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Id::new_non_max(index, gen),
+                Err(current) => cursor = current,
+            }
+        }
+
+        // the index of the last reserved Id must still fit below u32::MAX
+        let len = u32::try_from(self.entries.len()).ok();
+        let offset = self
+            .reserved_new
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |offset| {
+                let next = offset.checked_add(1)?;
+                len?.checked_add(next).map(|_| next)
+            })
+            .expect("Allocator::reserve: ran out of indices");
+        self.new_id(self.entries.len() as u32 + offset)
+    }
+
+    /// Makes all the Ids returned by [`Allocator::reserve`] alive
+    pub fn flush(&mut self) {
+        let cursor = std::mem::take(self.reserved_dead.get_mut());
+        if cursor != Cursor::UNRESERVED {
+            let end = Cursor::decode(cursor, None);
+            while self.next_dead != end && self.reuse_index().is_some() {}
+        }
+
+        let reserved_new = std::mem::take(self.reserved_new.get_mut());
+        self.entries.reserve(reserved_new as usize);
+        for _ in 0..reserved_new {
+            self.create_new();
         }
     }
 
     #[inline]
     pub fn kill(&mut self, id: Id<E>) -> bool {
-        if let Some(entry) = self.entries.get_mut(id.index()) {
-            if let Some(living) = entry.id() {
-                if id.eq(&living) {
-                    self.gen.increment(id);
+        self.flush();
+        if !self.is_alive(id) {
+            return false;
+        }
+
+        self.increment_gen(id);
+
+        if self.retire_slots && id.gen.checked_next().is_none() {
+            self.entries[id.index()] = Entry::Retired;
+            self.retired += 1;
+        } else {
+            self.push_dead(id.index, id.gen.next());
+        }
 
-                    *entry = Entry::Dead {
-                        next_dead: self.next_dead,
-                        gen: id.gen.next(),
-                    };
+        true
+    }
 
-                    self.next_dead = Some(id.index);
+    /// Adds the slot to the dead list in the position given by the [`FreeList`] policy
+    fn push_dead(&mut self, index: NonMaxU32, gen: GenType<E>) {
+        let next_dead = match self.free_list {
+            FreeList::Lifo => self.next_dead.replace(index),
+            FreeList::Fifo => {
+                match self.last_dead {
+                    Some(last) => *self.next_dead_mut(last) = Some(index),
+                    None => self.next_dead = Some(index),
+                }
+                None
+            }
+            FreeList::LowestIndex => {
+                let mut prev = None;
+                let mut next = self.next_dead;
+                while let Some(dead) = next.filter(|dead| *dead < index) {
+                    prev = Some(dead);
+                    next = *self.next_dead_mut(dead);
+                }
 
-                    return true;
+                match prev {
+                    Some(prev) => *self.next_dead_mut(prev) = Some(index),
+                    None => self.next_dead = Some(index),
                 }
+                next
             }
+        };
+
+        if next_dead.is_none() {
+            self.last_dead = Some(index);
+        }
+
+        self.entries[index.get() as usize] = Entry::Dead { next_dead, gen };
+    }
+
+    fn next_dead_mut(&mut self, index: NonMaxU32) -> &mut Option<NonMaxU32> {
+        match &mut self.entries[index.get() as usize] {
+            Entry::Dead { next_dead, .. } => next_dead,
+            _ => panic!("Allocator::next_dead_mut, dead index does not point to Entry::Dead"),
         }
+    }
 
-        false
+    fn increment_gen(&mut self, id: Id<E>) {
+        let before = self.gen.clone();
+        self.gen.increment(id);
+        self.history.push(before, id, &self.gen);
     }
 
     /// Drains the Vec, kills all the Ids, and filters out any duplicate or invalid Ids
@@ -105,10 +316,100 @@ impl<E: Entity<IdType = Dynamic>> Allocator<E> {
         // Take gen value after Ids are killed
         let after = self.gen.clone();
 
-        KilledIds {
-            ids: Valid::new(ids),
+        KilledIds { ids, before, after }
+    }
+
+    /// Empties the buffer and kills its Ids as with [`Allocator::kill_many`].
+    /// The Ids are sorted first, so the result doesn't depend on the order they were queued in
+    #[must_use]
+    #[inline]
+    pub fn kill_buffered(&mut self, buffer: &mut KillBuffer<E>) -> KilledIds<E> {
+        let ids = buffer.ids.get_mut().unwrap_or_else(PoisonError::into_inner);
+        ids.sort_unstable();
+        self.kill_many(ids)
+    }
+
+    /// Moves the living Ids into the lowest dead indices and truncates the allocator to fit them.
+    /// Returns the new Ids of the moved Ids, which must be applied to every collection.
+    ///
+    /// Retired slots are kept, and new slots start past the generations of the truncated slots,
+    /// so Ids that were killed before compacting never alias Ids created after it.
+    pub fn compact(&mut self) -> IdRemap<E> {
+        self.flush();
+        let before = self.gen.clone();
+
+        let mut moved = Vec::new();
+        let mut high = self.entries.len();
+
+        for low in 0..self.entries.len() {
+            let Entry::Dead { gen, .. } = self.entries[low] else {
+                continue;
+            };
+
+            // find the last living Id above the dead slot
+            while high > low + 1 && !matches!(self.entries[high - 1], Entry::Alive { .. }) {
+                high -= 1;
+            }
+
+            if high <= low + 1 {
+                break;
+            }
+
+            high -= 1;
+            if let Some(old) = self.entries[high].id() {
+                let new = Id::new(low as u32, gen);
+                self.entries[low] = Entry::from(new);
+
+                // moving an Id counts as killing it,
+                // so collections that miss the remap will fail to validate
+                self.increment_gen(old);
+                self.entries[high] = if self.retire_slots && old.gen.checked_next().is_none() {
+                    self.retired += 1;
+                    Entry::Retired
+                } else {
+                    Entry::Dead {
+                        next_dead: None,
+                        gen: old.gen.next(),
+                    }
+                };
+                moved.push((old, new));
+            }
+        }
+
+        let len = self
+            .entries
+            .iter()
+            .rposition(|e| !matches!(e, Entry::Dead { .. }))
+            .map_or(0, |i| i + 1);
+
+        for entry in self.entries.drain(len..) {
+            if let Entry::Dead { gen, .. } = entry {
+                self.first_gen = self.first_gen.max(Some(gen));
+            }
+        }
+
+        self.next_dead = None;
+        self.last_dead = None;
+        let dead = (0..len).filter(|i| matches!(self.entries[*i], Entry::Dead { .. }));
+        let dead = match self.free_list {
+            // appended to the back, so push the lowest index first
+            FreeList::Fifo => dead.collect::<Vec<_>>(),
+            // pushed to the front, so push the highest index first
+            FreeList::Lifo | FreeList::LowestIndex => dead.rev().collect(),
+        };
+        for index in dead {
+            if let Entry::Dead { gen, .. } = self.entries[index] {
+                self.push_dead(NonMaxU32::new(index as u32).unwrap(), gen);
+            }
+        }
+
+        moved.reverse();
+
+        IdRemap {
+            moved,
+            len,
             before,
-            after,
+            after: self.gen.clone(),
         }
     }
 
@@ -148,24 +449,176 @@ impl<E: Entity<IdType = Dynamic>> Allocator<E> {
         self.is_alive(id).then(|| Valid::new(id))
     }
 
+    /// The living Id at the index, if there is one
+    #[inline]
+    pub fn id_at(&self, index: usize) -> Option<Valid<'_, Id<E>>> {
+        self.entries.get(index)?.id().map(Valid::new)
+    }
+
+    /// Iterates over the living Ids that have a value in every collection of the query.
+    ///
+    /// Collections must be validated against this allocator before they can be joined.
+    #[inline]
+    pub fn join<'v, Q: Query<'v, Entity = E>>(&'v self, query: Q) -> Join<'v, Ids<'v, 'v, E>, Q> {
+        Join::new(self.ids(), query)
+    }
+
     /// Guarantees that no `Id<E>` can be killed during the `'valid` lifetime. Allows `Valid<Id<E>>`
     /// to have a longer lifetime than the immediate lifetime of `&'a self` or `&'a mut self`.
     #[inline]
     pub fn create_only(&mut self) -> &mut CreateOnly<E> {
+        self.flush();
         CreateOnly::ref_cast_mut(self)
     }
+
+    /// Copies the current state of the allocator,
+    /// so that it can be rolled back with [`Allocator::restore`].
+    /// Ids that are reserved but not yet flushed are not included.
+    #[inline]
+    pub fn snapshot(&self) -> AllocatorSnapshot<E> {
+        AllocatorSnapshot {
+            entries: self.entries.clone(),
+            next_dead: self.next_dead,
+            last_dead: self.last_dead,
+            gen: self.gen.clone(),
+            retired: self.retired,
+            first_gen: self.first_gen,
+        }
+    }
+
+    /// Rolls the allocator back to a snapshot, so that the same Ids are alive as when it was taken.
+    ///
+    /// Collections are not rolled back, and may hold Ids that were created after the snapshot,
+    /// whose values would be picked up by the new Ids once their indices are reissued.
+    /// So every collection must be restored along with the allocator, e.g., from a clone taken
+    /// with the snapshot, and then apply the returned `IdRemap<E>`, which moves no Ids.
+    /// Each restore gives the allocator a new `AllocGen<E>`,
+    /// so collections that were not restored fail validation.
+    #[inline]
+    pub fn restore(&mut self, snapshot: &AllocatorSnapshot<E>) -> IdRemap<E> {
+        self.entries.clone_from(&snapshot.entries);
+        self.next_dead = snapshot.next_dead;
+        self.last_dead = snapshot.last_dead;
+        self.gen.clone_from(&snapshot.gen);
+        self.retired = snapshot.retired;
+        self.first_gen = snapshot.first_gen;
+        self.history.restore(&self.gen);
+        *self.reserved_dead.get_mut() = Cursor::UNRESERVED;
+        *self.reserved_new.get_mut() = 0;
+
+        self.restores = self.restores.wrapping_add(1);
+        self.gen.increment_restore(self.restores);
+
+        IdRemap {
+            moved: Vec::new(),
+            len: self.entries.len(),
+            before: snapshot.gen.clone(),
+            after: self.gen.clone(),
+        }
+    }
+}
+
+/// The order in which an [`Allocator`] reuses the slots of killed Ids
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FreeList {
+    /// The most recently killed slot is reused first
+    #[default]
+    Lifo,
+    /// The least recently killed slot is reused first,
+    /// so the generations of frequently reused slots age more slowly
+    Fifo,
+    /// The slot with the lowest index is reused first, which keeps the living Ids compact.
+    /// Killing an Id walks the dead slots with lower indices.
+    LowestIndex,
+}
+
+/// Encodes the position of the reservation cursor in the dead list as a `u64`,
+/// so that it can be advanced with a single compare-exchange
+struct Cursor;
+
+impl Cursor {
+    /// Nothing has been reserved, so the cursor is at `Allocator::next_dead`
+    const UNRESERVED: u64 = 0;
+    /// Every dead index has been reserved
+    const EXHAUSTED: u64 = 1;
+
+    #[inline]
+    fn encode(index: Option<NonMaxU32>) -> u64 {
+        match index {
+            Some(index) => index.get() as u64 + 2,
+            None => Self::EXHAUSTED,
+        }
+    }
+
+    #[inline]
+    fn decode(cursor: u64, next_dead: Option<NonMaxU32>) -> Option<NonMaxU32> {
+        match cursor {
+            Self::UNRESERVED => next_dead,
+            Self::EXHAUSTED => None,
+            index => NonMaxU32::new((index - 2) as u32),
+        }
+    }
+}
+
+/// Never serializes the reservation cursor, checking that nothing has been reserved
+#[cfg(feature = "serde")]
+fn skip_reserved_dead(cursor: &AtomicU64) -> bool {
+    debug_assert_eq!(
+        Cursor::UNRESERVED,
+        cursor.load(Ordering::Relaxed),
+        "Allocator serialized with pending reservations, flush it first"
+    );
+    true
+}
+
+/// Never serializes the number of reserved entries, checking that nothing has been reserved
+#[cfg(feature = "serde")]
+fn skip_reserved_new(reserved_new: &AtomicU32) -> bool {
+    debug_assert_eq!(
+        0,
+        reserved_new.load(Ordering::Relaxed),
+        "Allocator serialized with pending reservations, flush it first"
+    );
+    true
+}
+
+/// The saved state of an [`Allocator`]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllocatorSnapshot<E: Entity> {
+    entries: Vec<Entry<GenType<E>>>,
+    next_dead: Option<NonMaxU32>,
+    last_dead: Option<NonMaxU32>,
+    gen: AllocGen<E>,
+    retired: u32,
+    first_gen: Option<GenType<E>>,
+}
+
+impl<E: Entity> Clone for AllocatorSnapshot<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            entries: self.entries.clone(),
+            next_dead: self.next_dead,
+            last_dead: self.last_dead,
+            gen: self.gen.clone(),
+            retired: self.retired,
+            first_gen: self.first_gen,
+        }
+    }
 }
 
 #[derive(Debug)]
-pub struct Ids<'slice, 'valid, E> {
-    iter: std::slice::Iter<'slice, Entry>,
+pub struct Ids<'slice, 'valid, E: Entity> {
+    iter: std::slice::Iter<'slice, Entry<GenType<E>>>,
     valid: PhantomData<&'valid ()>,
     entity: PhantomData<E>,
 }
 
-impl<'slice, E> Ids<'slice, '_, E> {
+impl<'slice, E: Entity> Ids<'slice, '_, E> {
     #[allow(clippy::ptr_arg)] // We want the whole vec
-    fn new(entries: &'slice Vec<Entry>) -> Self {
+    fn new(entries: &'slice Vec<Entry<GenType<E>>>) -> Self {
         Self {
             iter: entries.iter(),
             valid: PhantomData,
@@ -174,7 +627,7 @@ impl<'slice, E> Ids<'slice, '_, E> {
     }
 }
 
-impl<'valid, E: Entity<IdType = Dynamic>> Iterator for Ids<'_, 'valid, E> {
+impl<'valid, E: Entity<IdType: DynamicIdType>> Iterator for Ids<'_, 'valid, E> {
     type Item = Valid<'valid, Id<E>>;
 
     #[inline]
@@ -189,15 +642,15 @@ impl<'valid, E: Entity<IdType = Dynamic>> Iterator for Ids<'_, 'valid, E> {
 }
 
 #[derive(Debug)]
-pub struct SparseIds<'slice, 'valid, E> {
-    iter: std::slice::Iter<'slice, Entry>,
+pub struct SparseIds<'slice, 'valid, E: Entity> {
+    iter: std::slice::Iter<'slice, Entry<GenType<E>>>,
     valid: PhantomData<&'valid ()>,
     entity: PhantomData<E>,
 }
 
-impl<'slice, E> SparseIds<'slice, '_, E> {
+impl<'slice, E: Entity> SparseIds<'slice, '_, E> {
     #[allow(clippy::ptr_arg)] // We want the whole vec
-    fn new(entries: &'slice Vec<Entry>) -> Self {
+    fn new(entries: &'slice Vec<Entry<GenType<E>>>) -> Self {
         Self {
             iter: entries.iter(),
             valid: PhantomData,
@@ -206,7 +659,9 @@ impl<'slice, E> SparseIds<'slice, '_, E> {
     }
 }
 
-impl<'slice, 'valid, E: Entity<IdType = Dynamic>> IntoIterator for SparseIds<'slice, 'valid, E> {
+impl<'slice, 'valid, E: Entity<IdType: DynamicIdType>> IntoIterator
+    for SparseIds<'slice, 'valid, E>
+{
     type Item = Option<Valid<'valid, Id<E>>>;
     type IntoIter = SparseIdsIter<'slice, 'valid, E>;
 
@@ -216,22 +671,22 @@ impl<'slice, 'valid, E: Entity<IdType = Dynamic>> IntoIterator for SparseIds<'sl
     }
 }
 
-impl<E: Entity<IdType = Dynamic>> iter_context::ContextualIterator
+impl<E: Entity<IdType: DynamicIdType>> iter_context::ContextualIterator
     for SparseIds<'_, '_, E>
 {
     type Context = E;
 }
 
 #[derive(Debug)]
-pub struct SparseIdsIter<'slice, 'valid, E> {
-    iter: std::slice::Iter<'slice, Entry>,
+pub struct SparseIdsIter<'slice, 'valid, E: Entity> {
+    iter: std::slice::Iter<'slice, Entry<GenType<E>>>,
     valid: PhantomData<&'valid ()>,
     entity: PhantomData<E>,
 }
 
-impl<'slice, E> SparseIdsIter<'slice, '_, E> {
+impl<'slice, E: Entity> SparseIdsIter<'slice, '_, E> {
     #[allow(clippy::ptr_arg)] // We want the whole vec
-    fn new(iter: std::slice::Iter<'slice, Entry>) -> Self {
+    fn new(iter: std::slice::Iter<'slice, Entry<GenType<E>>>) -> Self {
         Self {
             iter,
             valid: PhantomData,
@@ -240,7 +695,7 @@ impl<'slice, E> SparseIdsIter<'slice, '_, E> {
     }
 }
 
-impl<'valid, E: Entity<IdType = Dynamic>> Iterator for SparseIdsIter<'_, 'valid, E> {
+impl<'valid, E: Entity<IdType: DynamicIdType>> Iterator for SparseIdsIter<'_, 'valid, E> {
     type Item = Option<Valid<'valid, Id<E>>>;
 
     #[inline]
@@ -249,13 +704,107 @@ impl<'valid, E: Entity<IdType = Dynamic>> Iterator for SparseIdsIter<'_, 'valid,
     }
 }
 
-impl<E: Entity<IdType = Dynamic>> AsRef<AllocGen<E>> for Allocator<E> {
+impl<E: Entity<IdType: DynamicIdType>> AsRef<AllocGen<E>> for Allocator<E> {
     fn as_ref(&self) -> &AllocGen<E> {
         &self.gen
     }
 }
 
-impl<'v, E: Entity<IdType = Dynamic>> Validator<'v, E> for &'v Allocator<E> {}
+impl<'v, E: Entity<IdType: DynamicIdType>> Validator<'v, E> for &'v Allocator<E> {
+    #[inline]
+    fn check(&self, gen: &AllocGen<E>) -> Result<(), SyncError<E>> {
+        if &self.gen == gen {
+            Ok(())
+        } else {
+            Err(self.history.sync_error(gen))
+        }
+    }
+}
+
+/// A bounded record of the Ids killed by an [`Allocator`], in the order they were killed
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct History<E: Entity> {
+    records: VecDeque<KillRecord<E>>,
+    capacity: usize,
+    /// The position of the allocator's current generation in the records,
+    /// which is before the end once the allocator has been restored to an earlier state
+    cursor: usize,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct KillRecord<E: Entity> {
+    before: AllocGen<E>,
+    id: Id<E>,
+    after: AllocGen<E>,
+}
+
+impl<E: Entity> Default for History<E> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            records: Default::default(),
+            capacity: 0,
+            cursor: 0,
+        }
+    }
+}
+
+impl<E: Entity> History<E> {
+    fn push(&mut self, before: AllocGen<E>, id: Id<E>, after: &AllocGen<E>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        // kills after a restore replace the kills that were rolled back
+        self.records.truncate(self.cursor);
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+
+        self.records.push_back(KillRecord {
+            before,
+            id,
+            after: after.clone(),
+        });
+        self.cursor = self.records.len();
+    }
+
+    fn restore(&mut self, gen: &AllocGen<E>) {
+        match self.position(gen) {
+            Some(position) => self.cursor = position,
+            None => {
+                self.records.clear();
+                self.cursor = 0;
+            }
+        }
+    }
+
+    fn position(&self, gen: &AllocGen<E>) -> Option<usize> {
+        let last = self.records.back().filter(|r| &r.after == gen);
+        self.records
+            .iter()
+            .position(|r| &r.before == gen)
+            .or_else(|| last.map(|_| self.records.len()))
+    }
+
+    fn ids(&self, range: std::ops::Range<usize>) -> Vec<Id<E>> {
+        self.records.range(range).map(|r| r.id).collect()
+    }
+
+    fn sync_error(&self, gen: &AllocGen<E>) -> SyncError<E> {
+        match self.position(gen) {
+            Some(p) if p < self.cursor => SyncError::Behind {
+                missed: self.ids(p..self.cursor),
+            },
+            Some(p) if p > self.cursor => SyncError::Ahead {
+                extra: self.ids(self.cursor..p),
+            },
+            _ => SyncError::Diverged,
+        }
+    }
+}
 
 unsafe impl<E: Entity> Send for Allocator<E> {}
 unsafe impl<E: Entity> Sync for Allocator<E> {}
@@ -267,12 +816,17 @@ pub struct CreateOnly<'valid, E: Entity> {
     marker: PhantomData<&'valid ()>,
 }
 
-impl<'valid, E: Entity<IdType = Dynamic>> CreateOnly<'valid, E> {
+impl<'valid, E: Entity<IdType: DynamicIdType>> CreateOnly<'valid, E> {
     #[inline]
     pub fn create(&mut self) -> Valid<'valid, Id<E>> {
         Valid::new(self.alloc.create().value)
     }
 
+    #[inline]
+    pub fn create_many(&mut self, count: usize) -> Valid<'valid, Vec<Id<E>>> {
+        Valid::new(self.alloc.create_many(count).value)
+    }
+
     #[inline]
     pub fn is_alive(&self, id: Id<E>) -> bool {
         self.alloc.is_alive(id)
@@ -292,37 +846,44 @@ impl<'valid, E: Entity<IdType = Dynamic>> CreateOnly<'valid, E> {
 unsafe impl<E: Entity> Send for CreateOnly<'_, E> {}
 unsafe impl<E: Entity> Sync for CreateOnly<'_, E> {}
 
-impl<E: Entity<IdType = Dynamic>> AsRef<AllocGen<E>> for CreateOnly<'_, E> {
+impl<E: Entity<IdType: DynamicIdType>> AsRef<AllocGen<E>> for CreateOnly<'_, E> {
     fn as_ref(&self) -> &AllocGen<E> {
         self.alloc.as_ref()
     }
 }
 
-impl<'v, E: Entity<IdType = Dynamic>> Validator<'v, E> for &CreateOnly<'v, E> {}
+impl<'v, E: Entity<IdType: DynamicIdType>> Validator<'v, E> for &CreateOnly<'v, E> {
+    #[inline]
+    fn check(&self, gen: &AllocGen<E>) -> Result<(), SyncError<E>> {
+        (&self.alloc).check(gen)
+    }
+}
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-enum Entry {
+enum Entry<G> {
     // Does not contain an Id so that the size is 8 instead of 12
     Alive {
         index: NonMaxU32,
-        gen: Gen,
+        gen: G,
     },
     Dead {
         next_dead: Option<NonMaxU32>,
-        gen: Gen,
+        gen: G,
     },
+    /// The generation was exhausted and the slot will not be reused
+    Retired,
 }
 
-impl<E: Entity<IdType = Dynamic>> From<Id<E>> for Entry {
+impl<E: Entity<IdType: DynamicIdType>> From<Id<E>> for Entry<GenType<E>> {
     fn from(id: Id<E>) -> Self {
         let Id { index, gen, .. } = id;
         Entry::Alive { index, gen }
     }
 }
 
-impl Entry {
-    fn id<E: Entity<IdType = Dynamic>>(&self) -> Option<Id<E>> {
+impl<G: Generation> Entry<G> {
+    fn id<E: Entity<IdType: IdType<Gen = G>>>(&self) -> Option<Id<E>> {
         if let &Entry::Alive { index, gen } = self {
             Some(Id::new_non_max(index, gen))
         } else {
@@ -331,18 +892,186 @@ impl Entry {
     }
 }
 
-/// A list of valid, unique Ids that have been killed.
+/// A list of unique Ids that have been killed.
+/// Includes before and after allocator generations for validating and updating AllocGen values
+pub struct KilledIds<E: Entity> {
+    ids: Vec<Id<E>>,
+    before: AllocGen<E>,
+    after: AllocGen<E>,
+}
+
+impl<E: Entity> KilledIds<E> {
+    /// The killed Ids, which are no longer alive and so are not `Valid`
+    #[inline]
+    pub fn ids(&self) -> &[Id<E>] {
+        &self.ids
+    }
+
+    #[inline]
+    pub fn iter(&self) -> std::slice::Iter<'_, Id<E>> {
+        self.ids.iter()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    #[inline]
+    pub fn before(&self) -> &AllocGen<E> {
+        &self.before
+    }
+
+    #[inline]
+    pub fn after(&self) -> &AllocGen<E> {
+        &self.after
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> KilledIds<E> {
+    /// Appends the batch of Ids that were killed right after this one,
+    /// so that both can be passed to collections at once.
+    /// With an order-independent checksum, the batches can be merged in any order
+    #[inline]
+    #[track_caller]
+    pub fn merge(&mut self, next: KilledIds<E>) {
+        self.after = self
+            .after
+            .after_kills(&next.before, &next.after)
+            .expect("KilledIds::merge: batches are not successive");
+
+        self.ids.extend(next.ids);
+    }
+}
+
+/// A collection that removes the Ids killed by its allocator to stay in sync with it.
+///
+/// Implemented for tuples of listeners,
+/// so that a batch of killed Ids can be passed to every collection of an entity with one call.
+pub trait KillListener<E: Entity<IdType: DynamicIdType>> {
+    fn kill_many(&mut self, killed: &KilledIds<E>);
+}
+
+impl<E: Entity<IdType: DynamicIdType>, L: KillListener<E> + ?Sized> KillListener<E> for &mut L {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        L::kill_many(self, killed);
+    }
+}
+
+macro_rules! impl_kill_listener {
+    ($($l:ident),+) => {
+        impl<E: Entity<IdType: DynamicIdType>, $($l: KillListener<E>),+> KillListener<E>
+            for ($($l,)+)
+        {
+            #[inline]
+            #[allow(non_snake_case)]
+            fn kill_many(&mut self, killed: &KilledIds<E>) {
+                let ($($l,)+) = self;
+                $($l.kill_many(killed);)+
+            }
+        }
+    };
+}
+
+impl_kill_listener!(A);
+impl_kill_listener!(A, B);
+impl_kill_listener!(A, B, C);
+impl_kill_listener!(A, B, C, D);
+impl_kill_listener!(A, B, C, D, F);
+impl_kill_listener!(A, B, C, D, F, G);
+impl_kill_listener!(A, B, C, D, F, G, H);
+impl_kill_listener!(A, B, C, D, F, G, H, I);
+
+/// Collects Ids to be killed through a shared reference, such as during a parallel pass,
+/// so that they can be killed together once the allocator is available again.
+///
+/// See [`Allocator::kill_buffered`]
+#[derive(Debug)]
+pub struct KillBuffer<E: Entity> {
+    ids: Mutex<Vec<Id<E>>>,
+}
+
+impl<E: Entity> Default for KillBuffer<E> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            ids: Default::default(),
+        }
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> KillBuffer<E> {
+    /// Queues the Id to be killed when the buffer is flushed
+    #[inline]
+    pub fn kill<V: ValidId<Entity = E>>(&self, id: V) {
+        self.lock().push(id.id());
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Vec<Id<E>>> {
+        // a panic while pushing cannot leave the Vec in an invalid state
+        self.ids.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, V: ValidId<Entity = E>> Extend<V> for KillBuffer<E> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = V>>(&mut self, iter: I) {
+        let ids = self.ids.get_mut().unwrap_or_else(PoisonError::into_inner);
+        ids.extend(iter.into_iter().map(|id| id.id()));
+    }
+}
+
+/// The Ids that were moved by [`Allocator::compact`], and where they were moved to,
+/// or the change of generation from [`Allocator::restore`], which moves no Ids.
 /// Includes before and after allocator generations for validating and updating AllocGen values
-pub struct KilledIds<'v, E: Entity> {
-    ids: Valid<'v, Vec<Id<E>>>,
+#[derive(Debug)]
+pub struct IdRemap<E: Entity> {
+    moved: Vec<(Id<E>, Id<E>)>,
+    len: usize,
     before: AllocGen<E>,
     after: AllocGen<E>,
 }
 
-impl<'v, E: Entity> KilledIds<'v, E> {
+impl<E: Entity> IdRemap<E> {
+    /// Pairs of old and new Ids, ordered by the old index
+    #[inline]
+    pub fn moved(&self) -> &[(Id<E>, Id<E>)] {
+        &self.moved
+    }
+
+    /// The new Id of a living Id, which is unchanged if it wasn't moved
+    #[inline]
+    pub fn get(&self, id: Id<E>) -> Id<E> {
+        match self
+            .moved
+            .binary_search_by_key(&id.index, |(old, _)| old.index)
+        {
+            Ok(i) if self.moved[i].0 == id => self.moved[i].1,
+            _ => id,
+        }
+    }
+
+    /// The number of slots left in the allocator
     #[inline]
-    pub fn ids(&self) -> Valid<'v, &Vec<Id<E>>> {
-        Valid::new(&self.ids.value)
+    pub fn slots(&self) -> usize {
+        self.len
     }
 
     #[inline]
@@ -360,7 +1089,7 @@ impl<'v, E: Entity> KilledIds<'v, E> {
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RangeAllocator<E> {
-    next: u32,
+    next: AtomicU32,
     marker: PhantomData<E>,
 }
 
@@ -375,7 +1104,7 @@ impl<E> Default for RangeAllocator<E> {
 }
 impl<E> PartialEq for RangeAllocator<E> {
     fn eq(&self, other: &Self) -> bool {
-        self.next.eq(&other.next)
+        self.next().eq(&other.next())
     }
 }
 
@@ -383,41 +1112,105 @@ impl<E> RangeAllocator<E> {
     #[inline]
     pub const fn new() -> Self {
         Self {
-            next: 0,
+            next: AtomicU32::new(0),
             marker: PhantomData,
         }
     }
+
+    #[inline]
+    fn next(&self) -> u32 {
+        self.next.load(Ordering::Relaxed)
+    }
 }
 
 impl<E: Entity<IdType = Static>> RangeAllocator<E> {
     #[inline]
     pub fn create(&mut self) -> Id<E> {
-        let id = Id::new(self.next, ());
-        self.next += 1;
+        let next = self.next.get_mut();
+        let id = Id::new(*next, ());
+        *next += 1;
         id
     }
 
     #[inline]
     pub fn create_range(&mut self, count: usize) -> IdRange<E> {
-        let start = self.next;
+        let next = self.next.get_mut();
+        let start = *next;
         let end = start + count as u32;
-        self.next = end;
+        *next = end;
         IdRange::new(start, end)
     }
 
+    /// Creates a range of Ids through a shared reference,
+    /// so that ranges can be created from many threads at once
+    #[inline]
+    #[track_caller]
+    pub fn reserve_range(&self, count: usize) -> IdRange<E> {
+        let start = self
+            .next
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |start| {
+                start.checked_add(u32::try_from(count).ok()?)
+            })
+            .expect("RangeAllocator::reserve_range: ran out of indices");
+        IdRange::new(start, start + count as u32)
+    }
+
     #[inline]
     pub fn ids(&self) -> IdRange<E> {
-        IdRange::new(0, self.next)
+        IdRange::new(0, self.next())
+    }
+
+    /// Iterates over the Ids that have a value in every collection of the query.
+    #[inline]
+    pub fn join<'v, Q: Query<'v, Entity = E>>(
+        &self,
+        query: Q,
+    ) -> Join<'v, ValidIter<'v, RangeIter<E>>, Q> {
+        Join::new(Valid::new(self.ids().into_iter()).into_iter(), query)
     }
 
     #[cfg(feature = "rayon")]
     #[inline]
     pub fn par_ids(&self) -> impl IndexedParallelIterator<Item = Id<E>> {
-        (0..self.next).into_par_iter().map(|i| Id::new(i, ()))
+        (0..self.next()).into_par_iter().map(|i| Id::new(i, ()))
     }
 }
 
-impl<E: Entity<IdType = Static>> IntoIterator for &RangeAllocator<E> {
+impl<E> RangeAllocator<E> {
+    /// Copies the current state of the allocator,
+    /// so that it can be rolled back with [`RangeAllocator::restore`]
+    #[inline]
+    pub fn snapshot(&self) -> RangeAllocatorSnapshot<E> {
+        RangeAllocatorSnapshot {
+            next: self.next(),
+            marker: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn restore(&mut self, snapshot: &RangeAllocatorSnapshot<E>) {
+        *self.next.get_mut() = snapshot.next;
+    }
+}
+
+/// The saved state of a [`RangeAllocator`]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RangeAllocatorSnapshot<E> {
+    next: u32,
+    marker: PhantomData<E>,
+}
+
+impl<E> Clone for RangeAllocatorSnapshot<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for RangeAllocatorSnapshot<E> {}
+
+impl<E: Entity<IdType = Static>> IntoIterator for &RangeAllocator<E> {
     type Item = Id<E>;
 
     type IntoIter = crate::id::RangeIter<E>;
@@ -434,12 +1227,31 @@ impl<E: Entity<IdType = Static>> ContextualIterator for &RangeAllocator<E> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests::{Dyn, Stat};
+    use crate::gen::{Gen, Gen32, Gen8};
+    use crate::tests::{Dyn, Dyn32, Dyn8, DynSum, Stat};
     use crate::valid::ValidId;
+    use crate::{DynComponent, IdMap, SparseComponent};
+    #[cfg(feature = "rayon")]
+    use rayon::prelude::ParallelIterator;
 
     #[test]
     fn entry_size() {
-        assert_eq!(8, std::mem::size_of::<Entry>());
+        assert_eq!(8, std::mem::size_of::<Entry<Gen>>());
+        assert_eq!(8, std::mem::size_of::<Entry<Gen8>>());
+        assert_eq!(12, std::mem::size_of::<Entry<Gen32>>());
+    }
+
+    #[test]
+    fn allocator_with_other_gen_widths() {
+        let mut alloc8 = Allocator::<Dyn8>::default();
+        let id = alloc8.create().value;
+        alloc8.kill(id);
+        assert_eq!(Id::new(0, Gen8::MIN.next()), alloc8.create().value);
+
+        let mut alloc32 = Allocator::<Dyn32>::default();
+        let id = alloc32.create().value;
+        alloc32.kill(id);
+        assert_eq!(Id::new(0, Gen32::MIN.next()), alloc32.create().value);
     }
 
     #[test]
@@ -653,7 +1465,7 @@ mod tests {
 
         let kill_many = alloc.kill_many(&mut kill);
 
-        assert_eq!(kill_many.ids.value, vec![id0]);
+        assert_eq!(kill_many.ids, vec![id0]);
     }
 
     #[test]
@@ -666,7 +1478,7 @@ mod tests {
 
         let kill_many = alloc.kill_many(&mut kill);
 
-        assert!(kill_many.ids.value.is_empty());
+        assert!(kill_many.ids.is_empty());
     }
 
     #[test]
@@ -684,4 +1496,632 @@ mod tests {
             ids
         );
     }
+
+    #[test]
+    fn allocator_restore() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let id0 = alloc.create().value;
+        let id1 = alloc.create().value;
+        alloc.kill(id0);
+
+        let snapshot = alloc.snapshot();
+        let expected = alloc.snapshot();
+
+        alloc.kill(id1);
+        let id2 = alloc.create().value;
+        let id3 = alloc.create().value;
+
+        let remap = alloc.restore(&snapshot);
+
+        assert!(!alloc.is_alive(id0));
+        assert!(alloc.is_alive(id1));
+        assert!(!alloc.is_alive(id2));
+        assert!(!alloc.is_alive(id3));
+        assert_eq!(&expected.gen, remap.before());
+        assert_eq!(remap.after(), &alloc.gen);
+        assert_ne!(expected.gen, alloc.gen);
+        assert!(remap.moved().is_empty());
+        assert_eq!(expected.entries, alloc.entries);
+        assert_eq!(expected.next_dead, alloc.next_dead);
+    }
+
+    #[test]
+    fn restore_when_collection_inserted_after_snapshot() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+
+        let snapshot = alloc.snapshot();
+        comp.insert(alloc.create(), 1);
+        let _ = alloc.restore(&snapshot);
+
+        assert_eq!(Some(SyncError::Diverged), comp.try_validate(&alloc).err());
+    }
+
+    #[test]
+    fn restore_with_collections() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+        comp.insert(alloc.create(), 0);
+
+        let snapshot = alloc.snapshot();
+        let comp_snapshot = comp.clone();
+        comp.insert(alloc.create(), 1);
+
+        comp.clone_from(&comp_snapshot);
+        let remap = alloc.restore(&snapshot);
+        comp.remap(&remap);
+
+        let id = alloc.create().value;
+        let comp = comp.validate(&alloc);
+        assert_eq!(None, comp.get(alloc.validate(id).unwrap()));
+    }
+
+    #[test]
+    fn restore_twice_from_same_snapshot() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let snapshot = alloc.snapshot();
+
+        let first = alloc.restore(&snapshot);
+        let second = alloc.restore(&snapshot);
+
+        assert_ne!(first.after(), second.after());
+    }
+
+    #[test]
+    fn allocator_restore_reissues_same_ids() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let id0 = alloc.create().value;
+        alloc.kill(id0);
+
+        let snapshot = alloc.snapshot();
+        let first = alloc.create().value;
+
+        alloc.restore(&snapshot);
+        let second = alloc.create().value;
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn range_alloc_restore() {
+        let mut alloc = RangeAllocator::<Stat>::default();
+        let _ = alloc.create();
+
+        let snapshot = alloc.snapshot();
+        let range = alloc.create_range(2);
+
+        alloc.restore(&snapshot);
+
+        assert_eq!(range, alloc.create_range(2));
+    }
+
+    #[test]
+    fn reserve_matches_create() {
+        let mut reserved = Allocator::<Dyn>::default();
+        let mut created = Allocator::<Dyn>::default();
+
+        for alloc in [&mut reserved, &mut created] {
+            let ids = alloc.create_many(4).value;
+            let _ = alloc.kill_many(&mut vec![ids[1], ids[2]]);
+        }
+
+        let ids = (0..3).map(|_| reserved.reserve()).collect::<Vec<_>>();
+        assert!(ids.iter().all(|id| !reserved.is_alive(*id)));
+
+        reserved.flush();
+
+        assert!(ids.iter().all(|id| reserved.is_alive(*id)));
+        assert_eq!(ids, created.create_many(3).value);
+        assert_eq!(reserved, created);
+    }
+
+    #[test]
+    fn create_flushes_reserved_ids() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let reserved = alloc.reserve();
+        let created = alloc.create().value;
+
+        assert_ne!(reserved, created);
+        assert!(alloc.is_alive(reserved));
+    }
+
+    #[test]
+    fn kill_reserved_id() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let id = alloc.reserve();
+
+        assert!(alloc.kill(id));
+        assert_eq!(0, alloc.ids().count());
+    }
+
+    #[test]
+    fn restore_discards_reserved_ids() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let snapshot = alloc.snapshot();
+        let id = alloc.reserve();
+
+        alloc.restore(&snapshot);
+        alloc.flush();
+
+        assert!(!alloc.is_alive(id));
+    }
+
+    #[test]
+    fn range_alloc_reserve_range() {
+        let mut alloc = RangeAllocator::<Stat>::default();
+        let _ = alloc.create();
+
+        let range = alloc.reserve_range(2);
+
+        assert_eq!(IdRange::new(1, 3), range);
+        assert_eq!(IdRange::new(0, 3), alloc.ids());
+    }
+
+    #[test]
+    fn range_alloc_reserve_range_up_to_last_index() {
+        let alloc = RangeAllocator::<Stat> {
+            next: AtomicU32::new(u32::MAX - 2),
+            marker: PhantomData,
+        };
+
+        let range = alloc.reserve_range(2);
+
+        assert_eq!(IdRange::new(u32::MAX - 2, u32::MAX), range);
+    }
+
+    #[test]
+    #[should_panic(expected = "ran out of indices")]
+    fn range_alloc_reserve_range_overflow() {
+        let alloc = RangeAllocator::<Stat> {
+            next: AtomicU32::new(u32::MAX - 2),
+            marker: PhantomData,
+        };
+
+        let _ = alloc.reserve_range(3);
+    }
+
+    #[test]
+    #[should_panic(expected = "ran out of indices")]
+    fn range_alloc_reserve_range_count_too_large() {
+        let alloc = RangeAllocator::<Stat>::default();
+        let _ = alloc.reserve_range(u32::MAX as usize + 1);
+    }
+
+    #[test]
+    fn reserve_last_index() {
+        let mut alloc = Allocator::<Dyn>::default();
+        *alloc.reserved_new.get_mut() = u32::MAX - 1;
+
+        assert_eq!(u32::MAX as usize - 1, alloc.reserve().index());
+    }
+
+    #[test]
+    #[should_panic(expected = "ran out of indices")]
+    fn reserve_past_last_index() {
+        let mut alloc = Allocator::<Dyn>::default();
+        *alloc.reserved_new.get_mut() = u32::MAX;
+
+        let _ = alloc.reserve();
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn reserve_in_parallel() {
+        use rayon::prelude::IntoParallelIterator;
+
+        let mut alloc = Allocator::<Dyn>::default();
+        let ids = alloc.create_many(100).value;
+        let _ = alloc.kill_many(&mut ids[..50].to_vec());
+
+        let mut reserved = (0..100)
+            .into_par_iter()
+            .map(|_| alloc.reserve())
+            .collect::<Vec<_>>();
+        alloc.flush();
+
+        reserved.sort_unstable();
+        reserved.dedup();
+        assert_eq!(100, reserved.len());
+        assert!(reserved.iter().all(|id| alloc.is_alive(*id)));
+        assert_eq!(150, alloc.ids().count());
+    }
+
+    fn reuse_order(free_list: FreeList) -> Vec<usize> {
+        let mut alloc = Allocator::<Dyn>::default().with_free_list(free_list);
+        let ids = alloc.create_many(5).value;
+        let _ = alloc.kill_many(&mut vec![ids[3], ids[1], ids[4]]);
+
+        let order = alloc.create_many(4).value;
+        order.into_iter().map(|id| id.index()).collect()
+    }
+
+    #[test]
+    fn free_list_lifo() {
+        assert_eq!(vec![4, 1, 3, 5], reuse_order(FreeList::Lifo));
+    }
+
+    #[test]
+    fn free_list_fifo() {
+        assert_eq!(vec![3, 1, 4, 5], reuse_order(FreeList::Fifo));
+    }
+
+    #[test]
+    fn free_list_lowest_index() {
+        assert_eq!(vec![1, 3, 4, 5], reuse_order(FreeList::LowestIndex));
+    }
+
+    #[test]
+    fn free_list_fifo_after_emptied() {
+        let mut alloc = Allocator::<Dyn>::default().with_free_list(FreeList::Fifo);
+        let ids = alloc.create_many(3).value;
+
+        alloc.kill(ids[0]);
+        let id0 = alloc.create().value;
+        alloc.kill(ids[2]);
+        alloc.kill(id0);
+
+        assert_eq!(2, alloc.create().value.index());
+        assert_eq!(0, alloc.create().value.index());
+    }
+
+    #[test]
+    fn free_list_does_not_affect_alloc_gen() {
+        let gens = [FreeList::Lifo, FreeList::Fifo, FreeList::LowestIndex].map(|free_list| {
+            let mut alloc = Allocator::<Dyn>::default().with_free_list(free_list);
+            let ids = alloc.create_many(4).value;
+            let _ = alloc.kill_many(&mut vec![ids[2], ids[0], ids[3]]);
+            alloc.gen
+        });
+
+        assert_eq!(gens[0], gens[1]);
+        assert_eq!(gens[0], gens[2]);
+    }
+
+    #[test]
+    fn reserve_follows_free_list() {
+        let mut alloc = Allocator::<Dyn>::default().with_free_list(FreeList::LowestIndex);
+        let ids = alloc.create_many(3).value;
+        let _ = alloc.kill_many(&mut vec![ids[2], ids[0]]);
+
+        assert_eq!(0, alloc.reserve().index());
+        assert_eq!(2, alloc.reserve().index());
+    }
+
+    #[test]
+    fn compact_moves_living_ids_down() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let ids = alloc.create_many(5).value;
+        let _ = alloc.kill_many(&mut vec![ids[0], ids[2]]);
+
+        let remap = alloc.compact();
+
+        assert_eq!(3, remap.slots());
+        assert_eq!(2, remap.moved().len());
+        assert_eq!(ids[1], remap.get(ids[1]));
+        assert_eq!(0, remap.get(ids[4]).index());
+        assert_eq!(2, remap.get(ids[3]).index());
+
+        let living = [ids[1], ids[3], ids[4]].map(|id| remap.get(id));
+        assert!(living.iter().all(|id| alloc.is_alive(*id)));
+        assert!(!alloc.is_alive(ids[4]));
+        assert_eq!(3, alloc.ids().count());
+        assert_eq!(3, alloc.create().value.index());
+    }
+
+    #[test]
+    fn compact_changes_alloc_gen() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let ids = alloc.create_many(2).value;
+        alloc.kill(ids[0]);
+        let before = alloc.gen.clone();
+
+        let remap = alloc.compact();
+
+        assert_eq!(&before, remap.before());
+        assert_ne!(remap.before(), remap.after());
+        assert_eq!(&alloc.gen, remap.after());
+    }
+
+    #[test]
+    fn compact_truncates_trailing_dead_slots() {
+        let mut alloc = Allocator::<Dyn8>::default().with_slot_retirement();
+        let ids = alloc.create_many(3).value;
+        alloc.kill(ids[2]);
+
+        let mut id = ids[1];
+        for _ in 0..u8::MAX {
+            alloc.kill(id);
+            id = alloc.create().value;
+        }
+        alloc.kill(id);
+        assert_eq!(1, alloc.retired());
+
+        let remap = alloc.compact();
+
+        assert!(remap.moved().is_empty());
+        assert_eq!(2, remap.slots());
+        assert_eq!(1, alloc.retired());
+        assert_eq!(2, alloc.create().value.index());
+    }
+
+    #[test]
+    fn compact_does_not_move_ids_into_retired_slots() {
+        let mut alloc = Allocator::<Dyn8>::default().with_slot_retirement();
+        let ids = alloc.create_many(2).value;
+
+        let stale = ids[0];
+        let mut id = stale;
+        for _ in 0..u8::MAX {
+            alloc.kill(id);
+            id = alloc.create().value;
+        }
+        alloc.kill(id);
+        assert_eq!(1, alloc.retired());
+        assert!(!alloc.is_alive(stale));
+
+        let remap = alloc.compact();
+
+        assert!(remap.moved().is_empty());
+        assert!(!alloc.is_alive(stale));
+        assert!(alloc.is_alive(ids[1]));
+    }
+
+    #[test]
+    fn compact_does_not_reissue_truncated_ids() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let ids = alloc.create_many(2).value;
+        alloc.kill(ids[1]);
+        let stale = alloc.create().value;
+        alloc.kill(stale);
+
+        alloc.compact();
+
+        let mut created = alloc.create_many(3).value;
+        created.push(alloc.reserve());
+        alloc.flush();
+        assert!(!alloc.is_alive(stale));
+        assert!(!alloc.is_alive(ids[1]));
+        assert!(created.iter().all(|id| alloc.is_alive(*id)));
+    }
+
+    #[test]
+    fn killed_ids_merge() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+        let ids = alloc.create_many(3).value;
+        for id in &ids {
+            comp.insert(Valid::new(id), 0);
+        }
+
+        let mut killed = alloc.kill_many(&mut vec![ids[0]]);
+        killed.merge(alloc.kill_many(&mut vec![ids[2], ids[0]]));
+
+        assert_eq!(2, killed.len());
+        assert_eq!(&alloc.gen, killed.after());
+
+        comp.kill_many(&killed);
+        comp.validate(&alloc);
+    }
+
+    #[test]
+    #[should_panic]
+    fn killed_ids_merge_out_of_order() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let ids = alloc.create_many(2).value;
+
+        let first = alloc.kill_many(&mut vec![ids[0]]);
+        let mut second = alloc.kill_many(&mut vec![ids[1]]);
+        second.merge(first);
+    }
+
+    #[test]
+    fn killed_ids_merge_in_any_order_with_order_independent_checksum() {
+        let mut alloc = Allocator::<DynSum>::default();
+        let mut comp = DynComponent::<DynSum, u32>::default();
+        let ids = alloc.create_many(3).value;
+        for id in &ids {
+            comp.insert(Valid::new(id), 0);
+        }
+
+        let first = alloc.kill_many(&mut vec![ids[0]]);
+        let mut second = alloc.kill_many(&mut vec![ids[2]]);
+        second.merge(first);
+
+        assert_eq!(2, second.len());
+        comp.kill_many(&second);
+        comp.validate(&alloc);
+    }
+
+    #[test]
+    fn kill_listener_tuple() {
+        struct World {
+            a: DynComponent<Dyn, u32>,
+            b: IdMap<Dyn, u32>,
+            c: SparseComponent<Dyn, u32>,
+        }
+
+        impl KillListener<Dyn> for World {
+            fn kill_many(&mut self, killed: &KilledIds<Dyn>) {
+                (&mut self.a, &mut self.b, &mut self.c).kill_many(killed);
+            }
+        }
+
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut world = World {
+            a: Default::default(),
+            b: Default::default(),
+            c: Default::default(),
+        };
+
+        let ids = alloc.create_many(2).value;
+        for id in &ids {
+            world.a.insert(Valid::new(id), 0);
+            world.b.insert(Valid::new(id), 1);
+            world.c.insert(Valid::new(id), 2);
+        }
+
+        let killed = alloc.kill_many(&mut vec![ids[1]]);
+        KillListener::kill_many(&mut world, &killed);
+
+        assert_eq!(1, world.b.validate(&alloc).value.len());
+        assert_eq!(1, world.c.validate(&alloc).value.len());
+        assert_eq!(None, world.a.validate(&alloc).get(Valid::new(ids[1])));
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut alloc = Allocator::<Dyn>::default().with_history(2);
+        let before = alloc.gen.clone();
+        let ids = alloc.create_many(3).value;
+        let _ = alloc.kill_many(&mut ids.clone());
+
+        assert_eq!(2, alloc.history.records.len());
+        assert_eq!(Err(SyncError::Diverged), Validator::check(&&alloc, &before));
+    }
+
+    #[test]
+    fn history_after_restore() {
+        let mut alloc = Allocator::<Dyn>::default().with_history(4);
+        let ids = alloc.create_many(3).value;
+
+        let snapshot = alloc.snapshot();
+        alloc.kill(ids[0]);
+        let rolled_back = alloc.gen.clone();
+        alloc.restore(&snapshot);
+        alloc.kill(ids[1]);
+
+        assert_eq!(1, alloc.history.records.len());
+        assert_eq!(
+            Err(SyncError::Diverged),
+            Validator::check(&&alloc, &rolled_back)
+        );
+    }
+
+    #[test]
+    fn allocator_wraps_exhausted_gen_by_default() {
+        let mut alloc = Allocator::<Dyn8>::default();
+        let first = alloc.create().value;
+        let mut id = first;
+
+        for _ in 0..u8::MAX {
+            alloc.kill(id);
+            id = alloc.create().value;
+        }
+
+        assert_eq!(first, id);
+        assert_eq!(0, alloc.retired());
+    }
+
+    #[test]
+    fn allocator_retires_exhausted_slot() {
+        let mut alloc = Allocator::<Dyn8>::default().with_slot_retirement();
+        let first = alloc.create().value;
+        let mut id = first;
+
+        for _ in 1..u8::MAX {
+            alloc.kill(id);
+            id = alloc.create().value;
+            assert_eq!(0, id.index());
+        }
+
+        assert!(alloc.kill(id));
+        assert_eq!(1, alloc.retired());
+
+        let next = alloc.create().value;
+        assert_eq!(1, next.index());
+        assert!(!alloc.is_alive(first));
+        assert!(!alloc.is_alive(id));
+    }
+
+    #[test]
+    fn create_many_fills_dead_slots_first() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let id0 = alloc.create().value;
+        let _id1 = alloc.create();
+        let id2 = alloc.create().value;
+        alloc.kill(id0);
+        alloc.kill(id2);
+
+        let ids = alloc.create_many(3).value;
+
+        assert_eq!(
+            vec![
+                Id::new(2, Gen::MIN.next()),
+                Id::new(0, Gen::MIN.next()),
+                Id::new(3, Gen::MIN)
+            ],
+            ids
+        );
+        assert_eq!(4, alloc.ids().count());
+    }
+
+    #[test]
+    fn create_only_create_many() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let alloc = alloc.create_only();
+
+        let first = alloc.create_many(2);
+        let second = alloc.create_many(2);
+
+        let ids = first.into_iter().chain(second).collect::<Vec<_>>();
+        assert_eq!(ids, alloc.ids().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn kill_buffered_filters_duplicates() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut buffer = KillBuffer::<Dyn>::default();
+
+        let ids = alloc.create_many(3);
+        buffer.kill(Valid::new(&ids.value[2]));
+        buffer.kill(Valid::new(&ids.value[0]));
+        buffer.kill(Valid::new(&ids.value[2]));
+        assert_eq!(3, buffer.len());
+
+        let ids = ids.value;
+        let killed = alloc.kill_buffered(&mut buffer);
+
+        assert!(buffer.is_empty());
+        assert_eq!(&[ids[0], ids[2]], killed.ids());
+        assert_eq!(
+            vec![ids[1]],
+            alloc.ids().map(|id| id.value).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn kill_buffered_ignores_queue_order() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn>::default();
+        let mut buffer_a = KillBuffer::<Dyn>::default();
+        let mut buffer_b = KillBuffer::<Dyn>::default();
+
+        let ids_a = a.create_many(2);
+        let ids_b = b.create_many(2);
+        buffer_a.extend(ids_a);
+        buffer_b.extend(ids_b.value.iter().rev().map(Valid::new));
+
+        let _ = a.kill_buffered(&mut buffer_a);
+        let _ = b.kill_buffered(&mut buffer_b);
+
+        assert_eq!(a, b);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn kill_buffer_in_parallel() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut buffer = KillBuffer::<Dyn>::default();
+        let _ = alloc.create_many(100);
+
+        alloc
+            .par_ids()
+            .filter(|id| id.value.index() % 2 == 0)
+            .for_each(|id| buffer.kill(id));
+
+        let killed = alloc.kill_buffered(&mut buffer);
+
+        assert_eq!(50, killed.len());
+        assert!(alloc.ids().all(|id| id.value.index() % 2 == 1));
+    }
 }
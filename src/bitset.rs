@@ -0,0 +1,499 @@
+use crate::allocator::{IdRemap, KillListener, KilledIds};
+use crate::gen::AllocGen;
+use crate::valid::{Synced, Validator};
+use crate::{Allocator, DynamicIdType, Entity, Id, Static, SyncError, Valid, ValidId};
+use iter_context::ContextualIterator;
+use std::marker::PhantomData;
+
+const BITS: usize = u64::BITS as usize;
+
+/// A dense set of Ids with one bit per index, for tag-like components such as `is_selected`.
+///
+/// Dynamic Ids are stored by index only,
+/// and are rebuilt from the allocator when the set is iterated.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdBitSet<E: Entity> {
+    words: Vec<u64>,
+    gen: AllocGen<E>,
+}
+
+impl<E: Entity> Default for IdBitSet<E> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            words: Default::default(),
+            gen: Default::default(),
+        }
+    }
+}
+
+impl<E: Entity> Clone for IdBitSet<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            words: self.words.clone(),
+            gen: self.gen.clone(),
+        }
+    }
+}
+
+impl<E: Entity> PartialEq for IdBitSet<E> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.indices().eq(other.indices()) && self.gen.eq(&other.gen)
+    }
+}
+
+impl<E: Entity> Eq for IdBitSet<E> {}
+
+impl<E: Entity> IdBitSet<E> {
+    /// Returns true if the Id was not already in the set
+    #[inline]
+    pub fn insert<V: ValidId<Entity = E>>(&mut self, id: V) -> bool {
+        let (word, mask) = Self::position(id.id().index());
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+
+        let inserted = self.words[word] & mask == 0;
+        self.words[word] |= mask;
+        inserted
+    }
+
+    /// Returns true if the Id was in the set
+    #[inline]
+    pub fn remove<V: ValidId<Entity = E>>(&mut self, id: V) -> bool {
+        self.remove_index(id.id().index())
+    }
+
+    #[inline]
+    fn remove_index(&mut self, index: usize) -> bool {
+        let (word, mask) = Self::position(index);
+        match self.words.get_mut(word) {
+            Some(bits) => {
+                let removed = *bits & mask != 0;
+                *bits &= !mask;
+                removed
+            }
+            None => false,
+        }
+    }
+
+    #[inline]
+    pub fn contains<V: ValidId<Entity = E>>(&self, id: V) -> bool {
+        self.contains_index(id.id().index())
+    }
+
+    #[inline]
+    fn contains_index(&self, index: usize) -> bool {
+        let (word, mask) = Self::position(index);
+        self.words.get(word).is_some_and(|bits| bits & mask != 0)
+    }
+
+    #[inline]
+    fn position(index: usize) -> (usize, u64) {
+        (index / BITS, 1 << (index % BITS))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|w| *w == 0)
+    }
+
+    /// The number of indices that can be held without allocating
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.words.len() * BITS
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    /// The indices in the set, in ascending order
+    #[inline]
+    pub fn indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut bits = word;
+            std::iter::from_fn(move || {
+                let bit = bits.trailing_zeros() as usize;
+                (bits != 0).then(|| {
+                    bits &= bits - 1;
+                    i * BITS + bit
+                })
+            })
+        })
+    }
+
+    /// Yields whether each of the first `len` indices is in the set,
+    /// padded with `false` past [`IdBitSet::capacity`],
+    /// so that it can be zipped with a component that is longer than the set
+    #[inline]
+    pub fn bits_padded(&self, len: usize) -> Bits<'_, E> {
+        Bits {
+            words: self.words.iter(),
+            word: 0,
+            remaining: 0,
+            len,
+            marker: PhantomData,
+        }
+    }
+
+    /// Adds the Ids that are in the other set
+    #[track_caller]
+    pub fn union_with(&mut self, other: &Self) {
+        self.assert_same_gen(other);
+        if self.words.len() < other.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a |= b;
+        }
+    }
+
+    /// Removes the Ids that are not in the other set
+    #[track_caller]
+    pub fn intersect_with(&mut self, other: &Self) {
+        self.assert_same_gen(other);
+        self.words.truncate(other.words.len());
+
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= b;
+        }
+    }
+
+    /// Removes the Ids that are in the other set
+    #[track_caller]
+    pub fn difference_with(&mut self, other: &Self) {
+        self.assert_same_gen(other);
+
+        for (a, b) in self.words.iter_mut().zip(&other.words) {
+            *a &= !b;
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn assert_same_gen(&self, other: &Self) {
+        assert_eq!(
+            self.gen,
+            other.gen,
+            "{}: sets are not in sync with each other",
+            std::any::type_name::<Self>()
+        );
+    }
+}
+
+impl<E: Entity<IdType = Static>> IdBitSet<E> {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = Id<E>> + '_ {
+        self.indices().map(|i| Id::new(i as u32, ()))
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> IdBitSet<E> {
+    #[inline]
+    pub fn kill<V: ValidId<Entity = E>>(&mut self, id: V) -> bool {
+        let id = id.id();
+        self.gen.increment(id);
+        self.remove_index(id.index())
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn kill_many(&mut self, killed: &KilledIds<E>) {
+        Synced::kill_many(self, killed);
+    }
+
+    /// Moves Ids to their new indices from a compacted allocator
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        assert_eq!(&self.gen, remap.before());
+        for (old, new) in remap.moved() {
+            if self.remove_index(old.index()) {
+                let (word, mask) = Self::position(new.index());
+                self.words[word] |= mask;
+            }
+        }
+        self.words.truncate(remap.slots().div_ceil(BITS));
+        self.gen.clone_from(remap.after());
+    }
+
+    /// Rebuilds the Ids in the set from the generations in the allocator
+    #[inline]
+    #[track_caller]
+    pub fn ids<'v>(&'v self, alloc: &'v Allocator<E>) -> impl Iterator<Item = Valid<'v, Id<E>>> {
+        let set = self.validate(alloc);
+        set.value.indices().filter_map(|i| alloc.id_at(i))
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate<'v, V: Validator<'v, E>>(&self, v: V) -> &Valid<'v, Self> {
+        Synced::validate(self, v)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_mut<'v, V: Validator<'v, E>>(&mut self, v: V) -> &mut Valid<'v, Self> {
+        Synced::validate_mut(self, v)
+    }
+
+    /// Returns how the collection is out of sync with the allocator instead of panicking
+    #[inline]
+    pub fn try_validate<'v, V: Validator<'v, E>>(
+        &self,
+        v: V,
+    ) -> Result<&Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate(self, v)
+    }
+
+    #[inline]
+    pub fn try_validate_mut<'v, V: Validator<'v, E>>(
+        &mut self,
+        v: V,
+    ) -> Result<&mut Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate_mut(self, v)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> Synced<E> for IdBitSet<E> {
+    #[inline]
+    fn alloc_gen(&self) -> &AllocGen<E> {
+        &self.gen
+    }
+
+    #[inline]
+    fn kill_id(&mut self, id: Id<E>) {
+        self.kill(Valid::new(id));
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> KillListener<E> for IdBitSet<E> {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        IdBitSet::kill_many(self, killed);
+    }
+}
+
+impl<E: Entity, V: ValidId<Entity = E>> FromIterator<V> for IdBitSet<E> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = V>>(iter: I) -> Self {
+        let mut set = Self::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<E: Entity, V: ValidId<Entity = E>> Extend<V> for IdBitSet<E> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = V>>(&mut self, iter: I) {
+        for id in iter {
+            self.insert(id);
+        }
+    }
+}
+
+/// Yields whether each index is in the set, up to [`IdBitSet::capacity`]
+impl<'a, E: Entity> IntoIterator for &'a IdBitSet<E> {
+    type Item = bool;
+    type IntoIter = Bits<'a, E>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.bits_padded(self.capacity())
+    }
+}
+
+impl<E: Entity> ContextualIterator for &IdBitSet<E> {
+    type Context = E;
+}
+
+/// `impl Iterator<Item = bool>`
+#[derive(Debug)]
+pub struct Bits<'a, E> {
+    words: std::slice::Iter<'a, u64>,
+    word: u64,
+    remaining: usize,
+    len: usize,
+    marker: PhantomData<E>,
+}
+
+impl<E> Iterator for Bits<'_, E> {
+    type Item = bool;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+
+        if self.remaining == 0 {
+            self.word = self.words.next().copied().unwrap_or(0);
+            self.remaining = BITS;
+        }
+
+        let bit = self.word & 1 != 0;
+        self.word >>= 1;
+        self.remaining -= 1;
+        self.len -= 1;
+        Some(bit)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<E> ExactSizeIterator for Bits<'_, E> {}
+
+impl<E: Entity> ContextualIterator for Bits<'_, E> {
+    type Context = E;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Dyn, Stat};
+    use crate::Component;
+
+    fn stat(indices: &[u32]) -> IdBitSet<Stat> {
+        indices.iter().map(|i| Id::new(*i, ())).collect()
+    }
+
+    #[test]
+    fn insert_remove_contains() {
+        let mut set = IdBitSet::<Stat>::default();
+        let id = Id::new(70, ());
+
+        assert!(set.insert(id));
+        assert!(!set.insert(id));
+        assert!(set.contains(id));
+        assert_eq!(1, set.len());
+        assert!(set.remove(id));
+        assert!(!set.contains(id));
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn word_operations() {
+        let a = stat(&[0, 1, 64, 130]);
+        let b = stat(&[1, 64, 65]);
+
+        let mut union = a.clone();
+        union.union_with(&b);
+        assert_eq!(vec![0, 1, 64, 65, 130], union.indices().collect::<Vec<_>>());
+
+        let mut intersection = a.clone();
+        intersection.intersect_with(&b);
+        assert_eq!(vec![1, 64], intersection.indices().collect::<Vec<_>>());
+
+        let mut difference = a.clone();
+        difference.difference_with(&b);
+        assert_eq!(vec![0, 130], difference.indices().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn iter_static() {
+        let set = stat(&[3, 1]);
+        assert_eq!(
+            vec![Id::new(1, ()), Id::new(3, ())],
+            set.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn ids_from_allocator() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut set = IdBitSet::<Dyn>::default();
+
+        let ids = a.create_many(3).value;
+        set.extend(ids.iter().map(Valid::new));
+
+        let killed = a.kill_many(&mut vec![ids[1]]);
+        set.kill_many(&killed);
+        let _new = a.create();
+
+        assert_eq!(vec![ids[0], ids[2]], set.ids(&a).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic]
+    fn ids_when_out_of_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut set = IdBitSet::<Dyn>::default();
+
+        let id = a.create();
+        set.insert(id);
+
+        let id = id.value;
+        a.kill(id);
+
+        set.ids(&a).count();
+    }
+
+    #[test]
+    fn zip_with_component() {
+        let set = stat(&[1]);
+        let mut comp = Component::<Stat, u32>::default();
+        for i in 0..3 {
+            comp.insert(Id::new(i, ()), i);
+        }
+
+        let selected = comp
+            .iter()
+            .zip(&set)
+            .into_iter()
+            .filter_map(|(value, selected)| selected.then_some(*value))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1], selected);
+    }
+
+    #[test]
+    fn zip_with_component_longer_than_set() {
+        let set = stat(&[1, 63]);
+        let mut comp = Component::<Stat, u32>::default();
+        for i in 0..130 {
+            comp.insert(Id::new(i, ()), i);
+        }
+
+        let zipped = comp
+            .iter()
+            .zip(set.bits_padded(comp.len()))
+            .into_iter()
+            .collect::<Vec<_>>();
+        assert_eq!(130, zipped.len());
+
+        let selected = zipped
+            .into_iter()
+            .filter_map(|(value, selected)| selected.then_some(*value))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![1, 63], selected);
+
+        let empty = IdBitSet::<Stat>::default();
+        let padded = empty.bits_padded(comp.len());
+        assert_eq!(130, comp.iter().zip(padded).into_iter().count());
+    }
+
+    #[test]
+    fn bits_end_at_capacity() {
+        let set = stat(&[1, 63]);
+
+        assert_eq!(64, (&set).into_iter().len());
+        assert_eq!(Some(true), (&set).into_iter().last());
+        assert_eq!(0, (&IdBitSet::<Stat>::default()).into_iter().count());
+        assert_eq!(
+            vec![false, true, false],
+            set.bits_padded(3).collect::<Vec<_>>()
+        );
+        assert_eq!(66, set.bits_padded(66).count());
+    }
+}
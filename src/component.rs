@@ -1,4 +1,7 @@
-use crate::{Entity, Id, IdRange, Static, ValidId};
+use crate::allocator::{IdRemap, KillListener, KilledIds};
+use crate::gen::AllocGen;
+use crate::valid::{Synced, Validator};
+use crate::{DynamicIdType, Entity, Id, IdRange, Static, SyncError, Valid, ValidId};
 use iter_context::ContextualIterator;
 use ref_cast::RefCast;
 use std::marker::PhantomData;
@@ -108,6 +111,18 @@ impl<E, T> RawComponent<E, T> {
     }
 }
 
+impl<E: Entity<IdType: DynamicIdType>, T> RawComponent<E, T> {
+    /// Moves values to the new indices of their Ids, and truncates to the compacted allocator
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        for (old, new) in remap.moved() {
+            if old.index() < self.values.len() {
+                self.values.swap(old.index(), new.index());
+            }
+        }
+        self.values.truncate(remap.slots());
+    }
+}
+
 impl<E: Entity, T> Index<Id<E>> for RawComponent<E, T> {
     type Output = T;
     #[inline]
@@ -301,6 +316,14 @@ impl<E, T> Component<E, T> {
     }
 }
 
+impl<E: Entity<IdType: DynamicIdType>, T> Component<E, T> {
+    /// Moves values to the new indices of their Ids, and truncates to the compacted allocator
+    #[inline]
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        self.values.remap(remap);
+    }
+}
+
 impl<E: Entity, T, V: ValidId<Entity = E>> Index<V> for Component<E, T> {
     type Output = T;
     #[inline]
@@ -378,6 +401,265 @@ impl<'a, E, T: Sync> rayon::prelude::IntoParallelRefIterator<'a> for &'a Compone
     }
 }
 
+#[cfg(feature = "rayon")]
+impl<'a, E, T: Send> rayon::prelude::IntoParallelRefMutIterator<'a> for &'a mut Component<E, T> {
+    type Iter = rayon::slice::IterMut<'a, T>;
+    type Item = &'a mut T;
+
+    #[inline]
+    fn par_iter_mut(&'a mut self) -> Self::Iter {
+        self.values.values.as_mut_slice().par_iter_mut()
+    }
+}
+
+/// A dense component for dynamic entities that tracks which Ids have been killed.
+///
+/// Values are removed when their Id is killed, so a `Valid<Id<E>>` can never index into
+/// a value left behind by a previous entity that used the same index.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DynComponent<E: Entity, T> {
+    values: RawComponent<E, Option<T>>,
+    gen: AllocGen<E>,
+}
+
+impl<E: Entity, T> Default for DynComponent<E, T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            values: RawComponent::default(),
+            gen: AllocGen::default(),
+        }
+    }
+}
+
+impl<E: Entity, T: Clone> Clone for DynComponent<E, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+            gen: self.gen.clone(),
+        }
+    }
+}
+
+impl<E: Entity, T: PartialEq> PartialEq for DynComponent<E, T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.values.eq(&other.values) && self.gen.eq(&other.gen)
+    }
+}
+
+impl<E: Entity, T: Eq> Eq for DynComponent<E, T> {}
+
+impl<E: Entity, T> DynComponent<E, T> {
+    #[inline]
+    pub fn insert<V: ValidId<Entity = E>>(&mut self, id: V, value: T) -> Option<T> {
+        let id = id.id();
+        match self.values.get_mut(id) {
+            Some(current) => current.replace(value),
+            None => {
+                self.values.insert_with(id, Some(value), || None);
+                None
+            }
+        }
+    }
+
+    #[inline]
+    pub fn remove<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<T> {
+        self.values.get_mut(id.id())?.take()
+    }
+
+    #[inline]
+    pub fn get<V: ValidId<Entity = E>>(&self, id: V) -> Option<&T> {
+        self.values.get(id.id())?.as_ref()
+    }
+
+    #[inline]
+    pub fn get_mut<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<&mut T> {
+        self.values.get_mut(id.id())?.as_mut()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> iter_context::Iter<'_, E, Option<T>> {
+        self.values.iter()
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> iter_context::IterMut<'_, E, Option<T>> {
+        self.values.iter_mut()
+    }
+
+    /// The number of slots, including those without a value
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> DynComponent<E, T> {
+    #[inline]
+    pub fn kill<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<T> {
+        let id = id.id();
+        self.gen.increment(id);
+        self.values.get_mut(id)?.take()
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn kill_many(&mut self, killed: &KilledIds<E>) {
+        Synced::kill_many(self, killed);
+    }
+
+    /// Moves values to the new indices of their Ids, and truncates to the compacted allocator
+    #[inline]
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        assert_eq!(&self.gen, remap.before());
+        self.values.remap(remap);
+        self.gen.clone_from(remap.after());
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate<'v, V: Validator<'v, E>>(&self, v: V) -> &Valid<'v, Self> {
+        Synced::validate(self, v)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_mut<'v, V: Validator<'v, E>>(&mut self, v: V) -> &mut Valid<'v, Self> {
+        Synced::validate_mut(self, v)
+    }
+
+    /// Returns how the collection is out of sync with the allocator instead of panicking
+    #[inline]
+    pub fn try_validate<'v, V: Validator<'v, E>>(
+        &self,
+        v: V,
+    ) -> Result<&Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate(self, v)
+    }
+
+    #[inline]
+    pub fn try_validate_mut<'v, V: Validator<'v, E>>(
+        &mut self,
+        v: V,
+    ) -> Result<&mut Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate_mut(self, v)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> Synced<E> for DynComponent<E, T> {
+    #[inline]
+    fn alloc_gen(&self) -> &AllocGen<E> {
+        &self.gen
+    }
+
+    #[inline]
+    fn kill_id(&mut self, id: Id<E>) {
+        self.kill(Valid::new(id));
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> KillListener<E> for DynComponent<E, T> {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        DynComponent::kill_many(self, killed);
+    }
+}
+
+impl<E: Entity, T, V: ValidId<Entity = E>> Index<V> for DynComponent<E, T> {
+    type Output = T;
+    #[inline]
+    #[track_caller]
+    fn index(&self, index: V) -> &Self::Output {
+        self.get(index).expect("no value for the given id")
+    }
+}
+
+impl<E: Entity, T, V: ValidId<Entity = E>> IndexMut<V> for DynComponent<E, T> {
+    #[inline]
+    #[track_caller]
+    fn index_mut(&mut self, index: V) -> &mut Self::Output {
+        self.get_mut(index).expect("no value for the given id")
+    }
+}
+
+impl<'v, E: Entity, T> Valid<'v, DynComponent<E, T>> {
+    #[inline]
+    pub fn get<V: ValidId<Entity = E>>(&self, id: V) -> Option<&T> {
+        self.value.get(id)
+    }
+
+    #[inline]
+    pub fn get_mut<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<&mut T> {
+        self.value.get_mut(id)
+    }
+}
+
+impl<E: Entity, T, V: ValidId<Entity = E>> Index<V> for Valid<'_, DynComponent<E, T>> {
+    type Output = T;
+    #[inline]
+    #[track_caller]
+    fn index(&self, index: V) -> &Self::Output {
+        self.value.index(index)
+    }
+}
+
+impl<E: Entity, T, V: ValidId<Entity = E>> IndexMut<V> for Valid<'_, DynComponent<E, T>> {
+    #[inline]
+    #[track_caller]
+    fn index_mut(&mut self, index: V) -> &mut Self::Output {
+        self.value.index_mut(index)
+    }
+}
+
+impl<'a, E: Entity, T> IntoIterator for &'a DynComponent<E, T> {
+    type Item = &'a Option<T>;
+    type IntoIter = std::slice::Iter<'a, Option<T>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        (&self.values).into_iter()
+    }
+}
+
+impl<'a, E: Entity, T> IntoIterator for &'a mut DynComponent<E, T> {
+    type Item = &'a mut Option<T>;
+    type IntoIter = std::slice::IterMut<'a, Option<T>>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        (&mut self.values).into_iter()
+    }
+}
+
+impl<E: Entity, T> ContextualIterator for &DynComponent<E, T> {
+    type Context = E;
+}
+
+impl<E: Entity, T> ContextualIterator for &mut DynComponent<E, T> {
+    type Context = E;
+}
+
+#[cfg(feature = "rayon")]
+impl<'a, E: Entity, T: Sync> rayon::prelude::IntoParallelRefIterator<'a>
+    for &'a DynComponent<E, T>
+{
+    type Iter = rayon::slice::Iter<'a, Option<T>>;
+    type Item = &'a Option<T>;
+
+    #[inline]
+    fn par_iter(&'a self) -> Self::Iter {
+        self.values.values.as_slice().par_iter()
+    }
+}
+
 macro_rules! impl_assign_op {
     ($t:ident, $f:ident) => {
         impl<C, T, M, MItem> std::ops::$t<M> for RawComponent<C, T>
@@ -555,7 +837,8 @@ impl<C, T: Copy> Component<C, T> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::tests::Stat;
+    use crate::tests::{Dyn, Stat};
+    use crate::Allocator;
 
     impl<E, T> From<Vec<T>> for RawComponent<E, T> {
         #[inline]
@@ -932,4 +1215,124 @@ mod tests {
         a += bc;
         assert_eq!(a.values.values, vec![3, 5, 8]);
     }
+
+    #[test]
+    fn dyn_component_insert_skipped() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let _id0 = alloc.create();
+        let id1 = alloc.create();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+
+        assert_eq!(None, comp.insert(id1, 1));
+        assert_eq!(2, comp.len());
+        assert_eq!(Some(&1), comp.get(id1));
+    }
+
+    #[test]
+    fn dyn_component_insert_returns_previous() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let id = alloc.create();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+
+        comp.insert(id, 1);
+
+        assert_eq!(Some(1), comp.insert(id, 2));
+        assert_eq!(2, comp[id]);
+    }
+
+    #[test]
+    fn dyn_component_remove() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let id = alloc.create();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+        comp.insert(id, 1);
+
+        assert_eq!(Some(1), comp.remove(id));
+        assert_eq!(None, comp.get(id));
+    }
+
+    #[test]
+    fn dyn_component_kill_removes_value() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let id = alloc.create();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+        comp.insert(id, 1);
+
+        assert_eq!(Some(1), comp.kill(id));
+        let id = id.value;
+        alloc.kill(id);
+        let id = alloc.create().value;
+
+        assert_eq!(None, comp.validate(&alloc).get(Valid::new(id)));
+    }
+
+    #[test]
+    fn dyn_component_kill_many() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let id0 = alloc.create().value;
+        let id1 = alloc.create().value;
+        let mut comp = DynComponent::<Dyn, u32>::default();
+        comp.insert(Valid::new(id0), 0);
+        comp.insert(Valid::new(id1), 1);
+
+        let killed = alloc.kill_many(&mut vec![id0]);
+        comp.kill_many(&killed);
+
+        let comp = comp.validate(&alloc);
+        assert_eq!(None, comp.get(Valid::new(id0)));
+        assert_eq!(Some(&1), comp.get(Valid::new(id1)));
+    }
+
+    #[test]
+    #[should_panic]
+    fn dyn_component_validate_when_out_of_sync() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+        let id = alloc.create();
+        comp.insert(id, 1);
+
+        let id = id.value;
+        alloc.kill(id);
+
+        comp.validate(&alloc);
+    }
+
+    #[test]
+    fn dyn_component_validate_mut_when_in_sync() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut comp = DynComponent::<Dyn, u32>::default();
+        let id = alloc.create();
+        comp.insert(id, 1);
+
+        comp.kill(id);
+        let id = id.value;
+        alloc.kill(id);
+
+        comp.validate_mut(&alloc);
+    }
+
+    #[test]
+    fn dyn_component_remap() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let ids = alloc.create_many(3).value;
+        let mut comp = DynComponent::<Dyn, u32>::default();
+        let mut dense = Component::<Dyn, u32>::default();
+        for (i, id) in ids.iter().enumerate() {
+            comp.insert(Valid::new(id), i as u32);
+            dense.insert(Valid::new(id), i as u32);
+        }
+
+        let killed = alloc.kill_many(&mut vec![ids[0]]);
+        comp.kill_many(&killed);
+
+        let remap = alloc.compact();
+        comp.remap(&remap);
+        dense.remap(&remap);
+
+        let comp = comp.validate(&alloc);
+        assert_eq!(2, comp.value.len());
+        assert_eq!(Some(&2), comp.get(Valid::new(remap.get(ids[2]))));
+        assert_eq!(Some(&1), comp.get(Valid::new(ids[1])));
+        assert_eq!(vec![2, 1], dense.into_iter().collect::<Vec<_>>());
+    }
 }
@@ -1,3 +1,6 @@
+use crate::gen::{Checksum, Crc32, Generation};
+use std::marker::PhantomData;
+
 /// Implement this trait for types to associate collections with that type.
 pub trait Entity: std::fmt::Debug + 'static {
     type IdType: IdType;
@@ -46,10 +49,22 @@ impl IdType for Static {
 
 /// Entity types with an IdType of Dynamic can be created and killed,
 /// and dynamic Ids need to be validated before they can be used to index into collections.
-pub struct Dynamic;
+///
+/// The generation defaults to 16 bits, and can be changed to [`crate::gen::Gen8`] for compact Ids
+/// or [`crate::gen::Gen32`] for indices that are reused very frequently, e.g., `Dynamic<Gen32>`.
+///
+/// The checksum defaults to [`Crc32`], which depends on the order of kills,
+/// and can be changed to [`crate::gen::Crc32Sum`] if collections apply kills in different orders,
+/// or to [`crate::gen::Fx64`] for a 64-bit checksum.
+pub struct Dynamic<G = crate::gen::Gen, C = Crc32>(PhantomData<(G, C)>);
 
-impl IdType for Dynamic {
-    type Gen = crate::gen::Gen;
-    type AllocGen = u32;
+impl<G: Generation, C: Checksum> IdType for Dynamic<G, C> {
+    type Gen = G;
+    type AllocGen = C;
     type Alloc<E: Entity> = crate::Allocator<E>;
 }
+
+impl<G: Generation, C: Checksum> DynamicIdType for Dynamic<G, C> {}
+
+/// Implemented by the IdTypes of entities that can be created and killed
+pub trait DynamicIdType: IdType<Gen: Generation, AllocGen: Checksum> {}
@@ -1,28 +1,197 @@
 use crate::id::Id;
-use crate::{entity::IdType, Dynamic, Entity};
+use crate::{entity::DynamicIdType, entity::IdType, Entity};
+use std::hash::Hasher;
 use std::marker::PhantomData;
-use std::num::NonZeroU16;
+use std::num::{NonZeroU16, NonZeroU32, NonZeroU8};
 
-/// Tracks the generation of dynamic entity Ids,
-/// allowing Ids that share the same index to be differentiated.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg(feature = "serde")]
+/// The generation of a dynamic entity Id, which is incremented each time its index is reused.
+pub trait Generation:
+    std::fmt::Debug
+    + Copy
+    + Eq
+    + std::hash::Hash
+    + Ord
+    + Send
+    + Sync
+    + serde::Serialize
+    + for<'de> serde::Deserialize<'de>
+    + 'static
+{
+    const MIN: Self;
+    type Bytes: AsRef<[u8]>;
+    /// Wraps around to `MIN` once the generation is exhausted
+    #[must_use]
+    fn next(self) -> Self;
+    /// Returns `None` once the generation is exhausted
+    #[must_use]
+    fn checked_next(self) -> Option<Self>;
+    fn to_ne_bytes(self) -> Self::Bytes;
+}
+#[cfg(not(feature = "serde"))]
+/// The generation of a dynamic entity Id, which is incremented each time its index is reused.
+pub trait Generation:
+    std::fmt::Debug + Copy + Eq + std::hash::Hash + Ord + Send + Sync + 'static
+{
+    const MIN: Self;
+    type Bytes: AsRef<[u8]>;
+    /// Wraps around to `MIN` once the generation is exhausted
+    #[must_use]
+    fn next(self) -> Self;
+    /// Returns `None` once the generation is exhausted
+    #[must_use]
+    fn checked_next(self) -> Option<Self>;
+    fn to_ne_bytes(self) -> Self::Bytes;
+}
+
+macro_rules! generation {
+    ($(#[$attr:meta])* $gen:ident, $non_zero:ident, $int:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct $gen($non_zero);
+
+        impl Generation for $gen {
+            const MIN: Self = unsafe { Self($non_zero::new_unchecked(1)) };
+            type Bytes = [u8; std::mem::size_of::<$int>()];
+
+            #[inline]
+            fn next(self) -> Self {
+                self.checked_next().unwrap_or(Self::MIN)
+            }
+
+            #[inline]
+            fn checked_next(self) -> Option<Self> {
+                self.0.checked_add(1).map(Self)
+            }
+
+            #[inline]
+            fn to_ne_bytes(self) -> Self::Bytes {
+                self.0.get().to_ne_bytes()
+            }
+        }
+    };
+}
+
+generation!(
+    /// Tracks the generation of dynamic entity Ids,
+    /// allowing Ids that share the same index to be differentiated.
+    Gen,
+    NonZeroU16,
+    u16
+);
+
+generation!(
+    /// An 8-bit generation for compact dynamic Ids,
+    /// which wraps around after 255 reuses of an index.
+    Gen8,
+    NonZeroU8,
+    u8
+);
+
+generation!(
+    /// A 32-bit generation for dynamic Ids whose indices are reused very frequently.
+    Gen32,
+    NonZeroU32,
+    u32
+);
+
+#[cfg(feature = "serde")]
+/// The running checksum of killed Ids that backs an [`AllocGen`].
+pub trait Checksum:
+    std::fmt::Debug
+    + Default
+    + Clone
+    + Eq
+    + Send
+    + Sync
+    + serde::Serialize
+    + for<'de> serde::Deserialize<'de>
+    + 'static
+{
+    fn increment(&mut self, index: u32, gen: &[u8]);
+    /// Returns this checksum moved by the change from `before` to `after`,
+    /// or `None` if the checksum depends on the order Ids are killed in
+    #[inline]
+    fn add_delta(&self, _before: &Self, _after: &Self) -> Option<Self> {
+        None
+    }
+}
+#[cfg(not(feature = "serde"))]
+/// The running checksum of killed Ids that backs an [`AllocGen`].
+pub trait Checksum: std::fmt::Debug + Default + Clone + Eq + Send + Sync + 'static {
+    fn increment(&mut self, index: u32, gen: &[u8]);
+    /// Returns this checksum moved by the change from `before` to `after`,
+    /// or `None` if the checksum depends on the order Ids are killed in
+    #[inline]
+    fn add_delta(&self, _before: &Self, _after: &Self) -> Option<Self> {
+        None
+    }
+}
+
+/// Chains the CRC32 of each killed Id, so checksums are only equal
+/// if the same Ids were killed in the same order.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct Gen(NonZeroU16);
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Crc32(u32);
 
-impl Gen {
-    pub(crate) const MIN: Self = unsafe { Self(NonZeroU16::new_unchecked(1)) };
+impl Checksum for Crc32 {
+    #[inline]
+    fn increment(&mut self, index: u32, gen: &[u8]) {
+        let mut hasher = crc32fast::Hasher::new_with_initial(self.0);
+        hasher.update(&index.to_ne_bytes());
+        hasher.update(gen);
+        self.0 = hasher.finalize();
+    }
+}
 
-    #[must_use]
-    pub(crate) fn next(self) -> Self {
-        NonZeroU16::new(self.0.get().wrapping_add(1))
-            .map(Self)
-            .unwrap_or(Self::MIN)
+/// Sums the CRC32 of each killed Id, so checksums are equal if the same Ids were killed
+/// in any order. Allows kills to be applied to collections in whatever order they arrive,
+/// e.g., per shard from parallel systems.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Crc32Sum(u32);
+
+impl Checksum for Crc32Sum {
+    #[inline]
+    fn increment(&mut self, index: u32, gen: &[u8]) {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&index.to_ne_bytes());
+        hasher.update(gen);
+        self.0 = self.0.wrapping_add(hasher.finalize());
+    }
+
+    #[inline]
+    fn add_delta(&self, before: &Self, after: &Self) -> Option<Self> {
+        Some(Self(self.0.wrapping_add(after.0.wrapping_sub(before.0))))
+    }
+}
+
+/// Chains a 64-bit FxHash of each killed Id, so checksums are only equal
+/// if the same Ids were killed in the same order.
+/// Makes accidental collisions far less likely than [`Crc32`] for long-running allocators.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Fx64(u64);
+
+impl Checksum for Fx64 {
+    #[inline]
+    fn increment(&mut self, index: u32, gen: &[u8]) {
+        let mut hasher = fxhash::FxHasher64::default();
+        hasher.write_u64(self.0);
+        hasher.write_u32(index);
+        hasher.write(gen);
+        self.0 = hasher.finish();
     }
 }
 
 /// A running checksum of IDs that have been killed.
 ///
-/// If two `AllocGen<E>` are equal, they have seen the same IDs killed (and also in the same order).
+/// If two `AllocGen<E>` are equal, they have seen the same IDs killed
+/// (and also in the same order, unless the [`Checksum`] is order-independent).
 ///
 /// If a collection of IDs can only have valid IDs added to it,
 /// and the allocator and collection agree on which IDs have been killed,
@@ -64,21 +233,41 @@ impl<E: Entity> PartialEq for AllocGen<E> {
 
 impl<E: Entity> Eq for AllocGen<E> {}
 
-impl<E: Entity<IdType = Dynamic>> AllocGen<E> {
+impl<E: Entity<IdType: DynamicIdType>> AllocGen<E> {
     pub(crate) fn increment(&mut self, id: Id<E>) {
-        let mut hasher = crc32fast::Hasher::new_with_initial(self.value);
-        hasher.update(&id.index.get().to_ne_bytes());
-        hasher.update(&id.gen.0.get().to_ne_bytes());
-        self.value = hasher.finalize();
+        self.value
+            .increment(id.index.get(), id.gen.to_ne_bytes().as_ref());
+    }
+
+    /// Marks a restore of the allocator, so that collections that were not restored with it
+    /// no longer match. Ids never have the index `u32::MAX`, so this can't match any kill
+    pub(crate) fn increment_restore(&mut self, restores: u32) {
+        self.value.increment(u32::MAX, &restores.to_ne_bytes());
+    }
+
+    /// Returns the value this reaches after a batch of kills that moved the allocator
+    /// from `before` to `after`.
+    ///
+    /// If the checksum is order-independent, the batch can follow any other kills.
+    /// Otherwise, returns `None` unless this equals `before`.
+    pub(crate) fn after_kills(&self, before: &Self, after: &Self) -> Option<Self> {
+        match self.value.add_delta(&before.value, &after.value) {
+            Some(value) => Some(Self {
+                value,
+                marker: PhantomData,
+            }),
+            None if self == before => Some(after.clone()),
+            None => None,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::gen::{AllocGen, Gen};
+    use crate::gen::{AllocGen, Gen, Gen32, Gen8, Generation};
     use crate::id::Id;
-    use crate::tests::Dyn;
-    use std::num::NonZeroU16;
+    use crate::tests::{Dyn, Dyn64, DynSum};
+    use std::num::{NonZeroU16, NonZeroU32, NonZeroU8};
 
     #[test]
     fn gen_next() {
@@ -90,6 +279,33 @@ mod tests {
         assert_eq!(first, last.next()); // wraps around to first
     }
 
+    #[test]
+    fn gen_checked_next() {
+        let first = Gen::MIN;
+        let last = Gen(NonZeroU16::new(u16::MAX).unwrap());
+
+        assert_eq!(Some(first.next()), first.checked_next());
+        assert_eq!(None, last.checked_next());
+    }
+
+    #[test]
+    fn gen8_next() {
+        let first = Gen8::MIN;
+        let last = Gen8(NonZeroU8::new(u8::MAX).unwrap());
+
+        assert_ne!(first, first.next());
+        assert_eq!(first, last.next());
+    }
+
+    #[test]
+    fn gen32_next() {
+        let first = Gen32::MIN;
+        let last = Gen32(NonZeroU32::new(u32::MAX).unwrap());
+
+        assert_ne!(first, first.next());
+        assert_eq!(first, last.next());
+    }
+
     #[test]
     fn alloc_gen_increment() {
         let mut alloc_gen = AllocGen::<Dyn>::default();
@@ -114,4 +330,47 @@ mod tests {
         assert_ne!(alloc_gen, unsynchronized);
         assert_ne!(alloc_gen, out_of_order);
     }
+
+    #[test]
+    fn alloc_gen_increment_order_independent() {
+        let mut in_order = AllocGen::<DynSum>::default();
+        let mut out_of_order = AllocGen::<DynSum>::default();
+        let unsynchronized = AllocGen::<DynSum>::default();
+
+        let id0 = Id::new(0, Gen::MIN);
+        let id1 = Id::new(1, Gen::MIN);
+
+        in_order.increment(id0);
+        in_order.increment(id1);
+
+        out_of_order.increment(id1);
+        out_of_order.increment(id0);
+
+        assert_eq!(in_order, out_of_order);
+        assert_ne!(in_order, unsynchronized);
+    }
+
+    #[test]
+    fn alloc_gen_increment_64_bit() {
+        let mut alloc_gen = AllocGen::<Dyn64>::default();
+        let mut synchronized = AllocGen::<Dyn64>::default();
+        let mut out_of_order = AllocGen::<Dyn64>::default();
+
+        let id0 = Id::new(0, Gen::MIN);
+        let id1 = Id::new(1, Gen::MIN);
+
+        alloc_gen.increment(id0);
+        alloc_gen.increment(id1);
+
+        synchronized.increment(id0);
+        synchronized.increment(id1);
+
+        out_of_order.increment(id1);
+        out_of_order.increment(id0);
+
+        assert_eq!(alloc_gen, synchronized);
+        assert_ne!(alloc_gen, AllocGen::default());
+        assert_ne!(alloc_gen, out_of_order);
+        assert_eq!(8, std::mem::size_of::<AllocGen<Dyn64>>());
+    }
 }
@@ -1,9 +1,10 @@
-use crate::{entity::IdType, Dynamic, Entity, Static};
+use crate::gen::Generation;
+use crate::{entity::IdType, DynamicIdType, Entity, Static};
 use std::cmp::Ordering;
 use std::iter::FusedIterator;
 use std::marker::PhantomData;
 
-type GenType<E> = <<E as Entity>::IdType as IdType>::Gen;
+pub(crate) type GenType<E> = <<E as Entity>::IdType as IdType>::Gen;
 
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -67,9 +68,9 @@ impl<E: Entity> Ord for Id<E> {
     }
 }
 
-impl<E: Entity<IdType = Dynamic>> Id<E> {
+impl<E: Entity<IdType: DynamicIdType>> Id<E> {
     pub(crate) fn first(index: u32) -> Self {
-        Self::new(index, crate::gen::Gen::MIN)
+        Self::new(index, Generation::MIN)
     }
 }
 
@@ -323,9 +324,9 @@ impl<E: Entity<IdType = Static>> FusedIterator for RangeIter<E> {}
 mod tests {
     use super::*;
     use crate::gen::Gen;
-    use crate::tests::{Dyn, Stat};
+    use crate::tests::{Dyn, Dyn32, Dyn8, Stat};
 
-    impl<E: Entity<IdType = Dynamic>> Id<E> {
+    impl<E: Entity<IdType: DynamicIdType>> Id<E> {
         pub(crate) fn next(self) -> Self {
             Self {
                 index: self.index,
@@ -358,6 +359,10 @@ mod tests {
         assert_eq!(4, size_of::<Option<Id<Stat>>>());
         assert_eq!(8, size_of::<Id<Dyn>>());
         assert_eq!(8, size_of::<Option<Id<Dyn>>>());
+        assert_eq!(8, size_of::<Id<Dyn8>>());
+        assert_eq!(8, size_of::<Option<Id<Dyn8>>>());
+        assert_eq!(8, size_of::<Id<Dyn32>>());
+        assert_eq!(8, size_of::<Option<Id<Dyn32>>>());
     }
 
     #[test]
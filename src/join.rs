@@ -0,0 +1,250 @@
+//! Iterating over the intersection of several collections that share an entity type.
+//!
+//! Collections of dynamic entities must be validated against the allocator that drives the join,
+//! so every `Valid<Id<E>>` yielded by the join is known to index into each of them correctly.
+
+use crate::component::DynComponent;
+use crate::{
+    Component, DynamicIdType, Entity, Id, IdMap, OrdIdMap, SparseComponent, Static, Valid,
+};
+use std::marker::PhantomData;
+
+/// A reference to a collection that can take part in a [`Join`].
+pub trait Fetch<'v> {
+    type Entity: Entity;
+    type Item;
+
+    /// # Safety
+    ///
+    /// Must not be called twice with the same Id while an item returned by an earlier call
+    /// is alive, as that would hand out aliasing mutable references.
+    unsafe fn fetch(&mut self, id: Valid<'v, Id<Self::Entity>>) -> Option<Self::Item>;
+}
+
+/// A tuple of [`Fetch`] types with the same entity,
+/// yielding an item only for Ids that are in all of them.
+pub trait Query<'v> {
+    type Entity: Entity;
+    type Item;
+
+    /// # Safety
+    ///
+    /// See [`Fetch::fetch`]
+    unsafe fn fetch(&mut self, id: Valid<'v, Id<Self::Entity>>) -> Option<Self::Item>;
+}
+
+/// `impl Iterator<Item = (Valid<Id<E>>, A::Item, B::Item, ...)>`
+pub struct Join<'v, I, Q> {
+    ids: I,
+    query: Q,
+    marker: PhantomData<&'v ()>,
+}
+
+impl<'v, I, Q> Join<'v, I, Q> {
+    /// `ids` must not contain duplicates
+    #[inline]
+    pub(crate) fn new(ids: I, query: Q) -> Self {
+        Self {
+            ids,
+            query,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'v, E, I, Q> Iterator for Join<'v, I, Q>
+where
+    E: Entity,
+    I: Iterator<Item = Valid<'v, Id<E>>>,
+    Q: Query<'v, Entity = E>,
+{
+    type Item = Q::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        for id in self.ids.by_ref() {
+            // Safety: the ids are unique, so each value is fetched at most once
+            if let Some(item) = unsafe { self.query.fetch(id) } {
+                return Some(item);
+            }
+        }
+        None
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.ids.size_hint().1)
+    }
+}
+
+macro_rules! impl_query {
+    ($($f:ident),+) => {
+        impl<'v, E: Entity, $($f: Fetch<'v, Entity = E>),+> Query<'v> for ($($f,)+) {
+            type Entity = E;
+            type Item = (Valid<'v, Id<E>>, $($f::Item),+);
+
+            #[inline]
+            #[allow(non_snake_case)]
+            unsafe fn fetch(&mut self, id: Valid<'v, Id<E>>) -> Option<Self::Item> {
+                let ($($f,)+) = self;
+                Some((id, $($f.fetch(id)?),+))
+            }
+        }
+    };
+}
+
+impl_query!(A);
+impl_query!(A, B);
+impl_query!(A, B, C);
+impl_query!(A, B, C, D);
+impl_query!(A, B, C, D, F);
+impl_query!(A, B, C, D, F, G);
+
+impl<'a, 'v, E: Entity<IdType = Static>, T> Fetch<'v> for &'a Component<E, T> {
+    type Entity = E;
+    type Item = &'a T;
+
+    #[inline]
+    unsafe fn fetch(&mut self, id: Valid<'v, Id<E>>) -> Option<Self::Item> {
+        self.get(id)
+    }
+}
+
+impl<'a, 'v, E: Entity<IdType = Static>, T> Fetch<'v> for &'a mut Component<E, T> {
+    type Entity = E;
+    type Item = &'a mut T;
+
+    #[inline]
+    unsafe fn fetch(&mut self, id: Valid<'v, Id<E>>) -> Option<Self::Item> {
+        self.get_mut(id)
+            .map(|value| unsafe { &mut *(value as *mut T) })
+    }
+}
+
+macro_rules! impl_fetch {
+    ($collection:ident) => {
+        impl<'a, 'v, E: Entity<IdType = Static>, T> Fetch<'v> for &'a $collection<E, T> {
+            type Entity = E;
+            type Item = &'a T;
+
+            #[inline]
+            unsafe fn fetch(&mut self, id: Valid<'v, Id<E>>) -> Option<Self::Item> {
+                self.get(id)
+            }
+        }
+
+        impl<'a, 'v, E: Entity<IdType = Static>, T> Fetch<'v> for &'a mut $collection<E, T> {
+            type Entity = E;
+            type Item = &'a mut T;
+
+            #[inline]
+            unsafe fn fetch(&mut self, id: Valid<'v, Id<E>>) -> Option<Self::Item> {
+                self.get_mut(id)
+                    .map(|value| unsafe { &mut *(value as *mut T) })
+            }
+        }
+
+        impl<'a, 'v, E: Entity<IdType: DynamicIdType>, T> Fetch<'v>
+            for &'a Valid<'v, $collection<E, T>>
+        {
+            type Entity = E;
+            type Item = &'a T;
+
+            #[inline]
+            unsafe fn fetch(&mut self, id: Valid<'v, Id<E>>) -> Option<Self::Item> {
+                self.value.get(id)
+            }
+        }
+
+        impl<'a, 'v, E: Entity<IdType: DynamicIdType>, T> Fetch<'v>
+            for &'a mut Valid<'v, $collection<E, T>>
+        {
+            type Entity = E;
+            type Item = &'a mut T;
+
+            #[inline]
+            unsafe fn fetch(&mut self, id: Valid<'v, Id<E>>) -> Option<Self::Item> {
+                self.value
+                    .get_mut(id)
+                    .map(|value| unsafe { &mut *(value as *mut T) })
+            }
+        }
+    };
+}
+
+impl_fetch!(DynComponent);
+impl_fetch!(IdMap);
+impl_fetch!(OrdIdMap);
+impl_fetch!(SparseComponent);
+
+#[cfg(test)]
+mod tests {
+    use crate::tests::{Dyn, Stat};
+    use crate::{
+        Allocator, Component, DynComponent, IdMap, RangeAllocator, SparseComponent, Valid,
+    };
+
+    #[test]
+    fn join_dynamic() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut a = DynComponent::<Dyn, u32>::default();
+        let mut b = IdMap::<Dyn, u32>::default();
+        let mut c = SparseComponent::<Dyn, u32>::default();
+
+        let id0 = Valid::new(alloc.create().value);
+        let id1 = Valid::new(alloc.create().value);
+        let id2 = Valid::new(alloc.create().value);
+
+        a.insert(id0, 0);
+        a.insert(id1, 1);
+        a.insert(id2, 2);
+        b.insert(id1, 10);
+        b.insert(id2, 20);
+        c.insert(id0, 100);
+        c.insert(id2, 200);
+
+        let a = a.validate(&alloc);
+        let b = b.validate_mut(&alloc);
+        let c = c.validate(&alloc);
+
+        for (_, a, b, c) in alloc.join((a, &mut *b, c)) {
+            *b += a + c;
+        }
+
+        assert_eq!(Some(&10), b.value.get(id1));
+        assert_eq!(Some(&222), b.value.get(id2));
+    }
+
+    #[test]
+    fn join_yields_ids() {
+        let mut alloc = Allocator::<Dyn>::default();
+        let mut a = DynComponent::<Dyn, u32>::default();
+
+        let _id0 = alloc.create();
+        let id1 = Valid::new(alloc.create().value);
+        a.insert(id1, 1);
+
+        let a = a.validate(&alloc);
+        let ids = alloc.join((a,)).map(|(id, _)| id).collect::<Vec<_>>();
+
+        assert_eq!(vec![id1], ids);
+    }
+
+    #[test]
+    fn join_static() {
+        let mut alloc = RangeAllocator::<Stat>::default();
+        let mut a = Component::<Stat, u32>::default();
+        let mut b = IdMap::<Stat, u32>::default();
+
+        for id in alloc.create_range(3) {
+            a.insert(id, id.index() as u32);
+        }
+        b.insert(alloc.ids().into_iter().next_back().unwrap(), 5);
+
+        for (_, a, b) in alloc.join((&mut a, &b)) {
+            *a += b;
+        }
+
+        assert_eq!(vec![0, 1, 7], a.into_iter().collect::<Vec<_>>());
+    }
+}
@@ -1,25 +1,38 @@
 extern crate core;
 
 pub mod allocator;
+mod bitset;
 pub mod component;
 pub mod entity;
 pub mod gen;
 mod id;
+pub mod join;
 mod map;
+mod multimap;
+mod ord_map;
 pub mod relations;
+mod set;
+mod sparse;
 mod valid;
 
 pub use allocator::{Allocator, RangeAllocator};
-pub use component::Component;
-pub use entity::{Dynamic, Entity, Static};
+pub use bitset::IdBitSet;
+pub use component::{Component, DynComponent};
+pub use entity::{Dynamic, DynamicIdType, Entity, Static};
 pub use id::{Id, IdRange};
 pub use iter_context::{ContextualIterator, FromContextualIterator};
+pub use join::Join;
 pub use map::IdMap;
+pub use multimap::IdMultiMap;
+pub use ord_map::OrdIdMap;
 pub use relations::*;
-pub use valid::{Valid, ValidId};
+pub use set::IdSet;
+pub use sparse::SparseComponent;
+pub use valid::{SyncError, Valid, ValidId};
 
 #[cfg(test)]
 pub mod tests {
+    use crate::gen::{Crc32Sum, Fx64, Gen, Gen32, Gen8};
     use crate::{Dynamic, Entity, Static};
 
     #[derive(Debug)]
@@ -29,6 +42,34 @@ pub mod tests {
         type IdType = Dynamic;
     }
 
+    #[derive(Debug)]
+    pub struct Dyn8;
+
+    impl Entity for Dyn8 {
+        type IdType = Dynamic<Gen8>;
+    }
+
+    #[derive(Debug)]
+    pub struct Dyn32;
+
+    impl Entity for Dyn32 {
+        type IdType = Dynamic<Gen32>;
+    }
+
+    #[derive(Debug)]
+    pub struct DynSum;
+
+    impl Entity for DynSum {
+        type IdType = Dynamic<Gen, Crc32Sum>;
+    }
+
+    #[derive(Debug)]
+    pub struct Dyn64;
+
+    impl Entity for Dyn64 {
+        type IdType = Dynamic<Gen, Fx64>;
+    }
+
     #[derive(Debug)]
     pub struct Stat;
 
@@ -1,7 +1,7 @@
-use crate::allocator::KilledIds;
+use crate::allocator::{IdRemap, KillListener, KilledIds};
 use crate::gen::AllocGen;
-use crate::valid::Validator;
-use crate::{Dynamic, Entity, Id, Valid, ValidId};
+use crate::valid::{Synced, Validator};
+use crate::{DynamicIdType, Entity, Id, SyncError, Valid, ValidId};
 use ref_cast::RefCast;
 
 #[derive(Debug)]
@@ -83,7 +83,7 @@ impl<E: Entity, T> RawIdMap<E, T> {
     }
 }
 
-impl<E: Entity<IdType = Dynamic>, T> RawIdMap<E, T> {
+impl<E: Entity<IdType: DynamicIdType>, T> RawIdMap<E, T> {
     #[inline]
     pub fn kill(&mut self, id: Id<E>) -> Option<T> {
         self.gen.increment(id);
@@ -91,12 +91,40 @@ impl<E: Entity<IdType = Dynamic>, T> RawIdMap<E, T> {
     }
 
     #[inline]
+    #[track_caller]
     pub fn kill_many(&mut self, killed: &KilledIds<E>) {
-        assert_eq!(&self.gen, killed.before());
-        for id in killed.ids() {
-            self.kill(*id.value);
+        Synced::kill_many(self, killed);
+    }
+
+    /// Moves values to the new Ids from a compacted allocator
+    #[inline]
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        assert_eq!(&self.gen, remap.before());
+        for (old, new) in remap.moved() {
+            if let Some(value) = self.map.remove(old) {
+                self.map.insert(*new, value);
+            }
         }
-        assert_eq!(&self.gen, killed.after());
+        self.gen.clone_from(remap.after());
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> Synced<E> for RawIdMap<E, T> {
+    #[inline]
+    fn alloc_gen(&self) -> &AllocGen<E> {
+        &self.gen
+    }
+
+    #[inline]
+    fn kill_id(&mut self, id: Id<E>) {
+        self.kill(id);
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> KillListener<E> for RawIdMap<E, T> {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        RawIdMap::kill_many(self, killed);
     }
 }
 
@@ -191,44 +219,73 @@ impl<E: Entity, T> IdMap<E, T> {
     }
 }
 
-impl<E: Entity<IdType = Dynamic>, T> IdMap<E, T> {
+impl<E: Entity<IdType: DynamicIdType>, T> IdMap<E, T> {
     #[inline]
+    #[track_caller]
     pub fn validate<'v, V: Validator<'v, E>>(&self, v: V) -> &Valid<'v, Self> {
-        assert_eq!(
-            &self.map.gen,
-            v.as_ref(),
-            "collection is out of sync with the allocator: {}",
-            std::any::type_name::<Self>()
-        );
-
-        let _ = v;
-        Valid::new_ref(self)
+        Synced::validate(self, v)
     }
 
     #[inline]
+    #[track_caller]
     pub fn validate_mut<'v, V: Validator<'v, E>>(&mut self, v: V) -> &mut Valid<'v, Self> {
-        assert_eq!(
-            &self.map.gen,
-            v.as_ref(),
-            "collection is out of sync with the allocator: {}",
-            std::any::type_name::<Self>()
-        );
+        Synced::validate_mut(self, v)
+    }
 
-        let _ = v;
-        Valid::new_mut(self)
+    /// Returns how the collection is out of sync with the allocator instead of panicking
+    #[inline]
+    pub fn try_validate<'v, V: Validator<'v, E>>(
+        &self,
+        v: V,
+    ) -> Result<&Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate(self, v)
+    }
+
+    #[inline]
+    pub fn try_validate_mut<'v, V: Validator<'v, E>>(
+        &mut self,
+        v: V,
+    ) -> Result<&mut Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate_mut(self, v)
     }
 }
 
-impl<E: Entity<IdType = Dynamic>, T> IdMap<E, T> {
+impl<E: Entity<IdType: DynamicIdType>, T> IdMap<E, T> {
     #[inline]
     pub fn kill<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<T> {
         self.map.kill(id.id())
     }
 
     #[inline]
+    #[track_caller]
     pub fn kill_many(&mut self, killed: &KilledIds<E>) {
         self.map.kill_many(killed);
     }
+
+    /// Moves values to the new Ids from a compacted allocator
+    #[inline]
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        self.map.remap(remap);
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> Synced<E> for IdMap<E, T> {
+    #[inline]
+    fn alloc_gen(&self) -> &AllocGen<E> {
+        &self.map.gen
+    }
+
+    #[inline]
+    fn kill_id(&mut self, id: Id<E>) {
+        self.map.kill(id);
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> KillListener<E> for IdMap<E, T> {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        IdMap::kill_many(self, killed);
+    }
 }
 
 impl<E: Entity, T, V: ValidId<Entity = E>> std::ops::Index<V> for IdMap<E, T> {
@@ -254,7 +311,8 @@ impl<'v, E: Entity, T> Valid<'v, IdMap<E, T>> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{tests::Dyn, Allocator};
+    use crate::tests::{Dyn, DynSum};
+    use crate::Allocator;
 
     #[test]
     #[should_panic]
@@ -315,4 +373,124 @@ mod tests {
 
         map.validate_mut(&a);
     }
+
+    #[test]
+    fn remap() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut map = IdMap::<Dyn, u32>::default();
+
+        let ids = a.create_many(2).value;
+        map.insert(Valid::new(ids[1]), 1);
+
+        let killed = a.kill_many(&mut vec![ids[0]]);
+        map.kill_many(&killed);
+
+        let remap = a.compact();
+        map.remap(&remap);
+
+        let map = &map.validate(&a).value;
+        assert_eq!(Some(&1), map.get(Valid::new(remap.get(ids[1]))));
+        assert_eq!(None, map.get(Valid::new(ids[1])));
+    }
+
+    #[test]
+    fn try_validate_when_behind() {
+        let mut a = Allocator::<Dyn>::default().with_history(4);
+        let map = IdMap::<Dyn, ()>::default();
+
+        let ids = a.create_many(2).value;
+        let _ = a.kill_many(&mut ids.clone());
+
+        let error = map.try_validate(&a).unwrap_err();
+        assert_eq!(SyncError::Behind { missed: ids }, error);
+    }
+
+    #[test]
+    fn try_validate_when_ahead() {
+        let mut a = Allocator::<Dyn>::default().with_history(4);
+        let mut map = IdMap::<Dyn, ()>::default();
+
+        let id = a.create().value;
+        let snapshot = a.snapshot();
+        let killed = a.kill_many(&mut vec![id]);
+        map.kill_many(&killed);
+        a.restore(&snapshot);
+
+        let error = map.try_validate(&a).unwrap_err();
+        assert_eq!(SyncError::Ahead { extra: vec![id] }, error);
+    }
+
+    #[test]
+    fn try_validate_when_diverged() {
+        let mut a = Allocator::<Dyn>::default().with_history(4);
+        let mut map = IdMap::<Dyn, ()>::default();
+
+        let ids = a.create_many(2).value;
+        a.kill(ids[0]);
+        map.kill(Valid::new(ids[1]));
+
+        assert_eq!(Some(SyncError::Diverged), map.try_validate(&a).err());
+    }
+
+    #[test]
+    fn try_validate_without_history() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut map = IdMap::<Dyn, ()>::default();
+
+        let id = a.create().value;
+        a.kill(id);
+
+        assert_eq!(Some(SyncError::Diverged), map.try_validate(&a).err());
+        map.kill(Valid::new(id));
+        assert!(map.try_validate_mut(&a).is_ok());
+    }
+
+    #[test]
+    fn kill_in_any_order_with_order_independent_checksum() {
+        let mut a = Allocator::<DynSum>::default();
+        let mut map = IdMap::<DynSum, ()>::default();
+
+        let ids = a.create_many(2).value;
+        let _ = a.kill_many(&mut ids.clone());
+
+        map.kill(Valid::new(ids[1]));
+        map.kill(Valid::new(ids[0]));
+
+        map.validate(&a);
+    }
+
+    #[test]
+    fn kill_many_batches_in_any_order_with_order_independent_checksum() {
+        let mut a = Allocator::<DynSum>::default();
+        let mut map = RawIdMap::<DynSum, u32>::default();
+
+        let ids = a.create_many(4).value;
+        for (i, id) in ids.iter().enumerate() {
+            map.insert(*id, i as u32);
+        }
+
+        let first = a.kill_many(&mut vec![ids[0], ids[1]]);
+        let second = a.kill_many(&mut vec![ids[3]]);
+
+        map.kill_many(&second);
+        map.kill_many(&first);
+
+        map.id_map().validate(&a);
+        assert_eq!(Some(&2), map.get(ids[2]));
+        assert_eq!(1, map.len());
+    }
+
+    #[test]
+    #[should_panic]
+    fn kill_many_batches_out_of_order() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut map = RawIdMap::<Dyn, u32>::default();
+
+        let ids = a.create_many(2).value;
+        let first = a.kill_many(&mut vec![ids[0]]);
+        let second = a.kill_many(&mut vec![ids[1]]);
+
+        map.kill_many(&second);
+        map.kill_many(&first);
+    }
 }
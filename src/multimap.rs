@@ -0,0 +1,468 @@
+use crate::allocator::{IdRemap, KilledIds};
+use crate::gen::AllocGen;
+use crate::valid::{kill_each, Validator};
+use crate::{DynamicIdType, Entity, Id, Static, SyncError, Valid, ValidId};
+use fxhash::FxHashMap;
+
+/// Maps each `Id<A>` to many `Id<B>`, such as the contents of an inventory.
+///
+/// Tracks the killed Ids of both entity types, so that a value is removed when it is killed,
+/// and a key is removed along with all of its values when it is killed.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdMultiMap<A: Entity, B: Entity> {
+    values: FxHashMap<Id<A>, Vec<Id<B>>>,
+    keys: FxHashMap<Id<B>, Vec<Id<A>>>,
+    key_gen: AllocGen<A>,
+    value_gen: AllocGen<B>,
+}
+
+impl<A: Entity, B: Entity> Default for IdMultiMap<A, B> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            values: Default::default(),
+            keys: Default::default(),
+            key_gen: Default::default(),
+            value_gen: Default::default(),
+        }
+    }
+}
+
+impl<A: Entity, B: Entity> Clone for IdMultiMap<A, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            values: self.values.clone(),
+            keys: self.keys.clone(),
+            key_gen: self.key_gen.clone(),
+            value_gen: self.value_gen.clone(),
+        }
+    }
+}
+
+impl<A: Entity, B: Entity> IdMultiMap<A, B> {
+    /// Returns true if the value was not already under the key
+    pub fn insert<K: ValidId<Entity = A>, V: ValidId<Entity = B>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> bool {
+        let (key, value) = (key.id(), value.id());
+        let values = self.values.entry(key).or_default();
+        if values.contains(&value) {
+            return false;
+        }
+
+        values.push(value);
+        self.keys.entry(value).or_default().push(key);
+        true
+    }
+
+    /// Returns true if the value was under the key
+    pub fn remove_value<K: ValidId<Entity = A>, V: ValidId<Entity = B>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> bool {
+        let (key, value) = (key.id(), value.id());
+        let removed = remove_from(&mut self.values, key, value);
+        if removed {
+            remove_from(&mut self.keys, value, key);
+        }
+        removed
+    }
+
+    /// Removes the key and returns its values
+    pub fn remove<K: ValidId<Entity = A>>(&mut self, key: K) -> Vec<Id<B>> {
+        self.remove_key(key.id())
+    }
+
+    fn remove_key(&mut self, key: Id<A>) -> Vec<Id<B>> {
+        let values = self.values.remove(&key).unwrap_or_default();
+        for value in &values {
+            remove_from(&mut self.keys, *value, key);
+        }
+        values
+    }
+
+    fn remove_all_values(&mut self, value: Id<B>) {
+        for key in self.keys.remove(&value).unwrap_or_default() {
+            remove_from(&mut self.values, key, value);
+        }
+    }
+
+    /// The values under the key, in the order they were inserted
+    #[inline]
+    pub fn get_all<K: ValidId<Entity = A>>(&self, key: K) -> &[Id<B>] {
+        self.values.get(&key.id()).map_or(&[], Vec::as_slice)
+    }
+
+    /// The keys that the value is under
+    #[inline]
+    pub fn keys_of<V: ValidId<Entity = B>>(&self, value: V) -> &[Id<A>] {
+        self.keys.get(&value.id()).map_or(&[], Vec::as_slice)
+    }
+
+    #[inline]
+    pub fn contains<K: ValidId<Entity = A>, V: ValidId<Entity = B>>(
+        &self,
+        key: K,
+        value: V,
+    ) -> bool {
+        self.get_all(key).contains(&value.id())
+    }
+
+    /// The number of keys with at least one value
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&Id<A>, &[Id<B>])> + '_ {
+        self.values.iter().map(|(k, v)| (k, v.as_slice()))
+    }
+}
+
+/// Removes the item from the list under the key, and the key once its list is empty
+fn remove_from<K: Eq + std::hash::Hash, T: PartialEq>(
+    map: &mut FxHashMap<K, Vec<T>>,
+    key: K,
+    item: T,
+) -> bool {
+    let std::collections::hash_map::Entry::Occupied(mut entry) = map.entry(key) else {
+        return false;
+    };
+
+    let Some(index) = entry.get().iter().position(|i| *i == item) else {
+        return false;
+    };
+
+    entry.get_mut().remove(index);
+    if entry.get().is_empty() {
+        entry.remove();
+    }
+    true
+}
+
+impl<A: Entity<IdType: DynamicIdType>, B: Entity> IdMultiMap<A, B> {
+    /// Removes the key and all of its values
+    #[inline]
+    pub fn kill_key<K: ValidId<Entity = A>>(&mut self, key: K) -> Vec<Id<B>> {
+        let key = key.id();
+        self.key_gen.increment(key);
+        self.remove_key(key)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn kill_many_keys(&mut self, killed: &KilledIds<A>) {
+        kill_each(
+            self,
+            |map| &map.key_gen,
+            killed,
+            |map, id| map.kill_key(Valid::new(id)),
+        );
+    }
+
+    /// Moves values to the new keys from a compacted allocator
+    pub fn remap_keys(&mut self, remap: &IdRemap<A>) {
+        assert_eq!(&self.key_gen, remap.before());
+        for (old, new) in remap.moved() {
+            if let Some(values) = self.values.remove(old) {
+                for value in &values {
+                    for key in self.keys.get_mut(value).into_iter().flatten() {
+                        if key == old {
+                            *key = *new;
+                        }
+                    }
+                }
+                self.values.insert(*new, values);
+            }
+        }
+        self.key_gen.clone_from(remap.after());
+    }
+
+    /// Returns how the keys are out of sync with their allocator
+    #[inline]
+    pub fn try_validate_keys<'v, V: Validator<'v, A>>(&self, v: V) -> Result<(), SyncError<A>> {
+        v.check(&self.key_gen)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn assert_keys_valid<'v, V: Validator<'v, A>>(&self, v: V) {
+        if let Err(error) = self.try_validate_keys(v) {
+            panic!(
+                "keys are out of sync with the allocator: {}: {error}",
+                std::any::type_name::<Self>()
+            );
+        }
+    }
+}
+
+impl<A: Entity, B: Entity<IdType: DynamicIdType>> IdMultiMap<A, B> {
+    /// Removes the value from every key that it is under
+    #[inline]
+    pub fn kill_value<V: ValidId<Entity = B>>(&mut self, value: V) {
+        let value = value.id();
+        self.value_gen.increment(value);
+        self.remove_all_values(value);
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn kill_many_values(&mut self, killed: &KilledIds<B>) {
+        kill_each(
+            self,
+            |map| &map.value_gen,
+            killed,
+            |map, id| map.kill_value(Valid::new(id)),
+        );
+    }
+
+    /// Moves keys to the new values from a compacted allocator
+    pub fn remap_values(&mut self, remap: &IdRemap<B>) {
+        assert_eq!(&self.value_gen, remap.before());
+        for (old, new) in remap.moved() {
+            if let Some(keys) = self.keys.remove(old) {
+                for key in &keys {
+                    for value in self.values.get_mut(key).into_iter().flatten() {
+                        if value == old {
+                            *value = *new;
+                        }
+                    }
+                }
+                self.keys.insert(*new, keys);
+            }
+        }
+        self.value_gen.clone_from(remap.after());
+    }
+
+    /// Returns how the values are out of sync with their allocator
+    #[inline]
+    pub fn try_validate_values<'v, V: Validator<'v, B>>(&self, v: V) -> Result<(), SyncError<B>> {
+        v.check(&self.value_gen)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn assert_values_valid<'v, V: Validator<'v, B>>(&self, v: V) {
+        if let Err(error) = self.try_validate_values(v) {
+            panic!(
+                "values are out of sync with the allocator: {}: {error}",
+                std::any::type_name::<Self>()
+            );
+        }
+    }
+}
+
+impl<A: Entity<IdType: DynamicIdType>, B: Entity<IdType: DynamicIdType>> IdMultiMap<A, B> {
+    #[inline]
+    #[track_caller]
+    pub fn validate<'v, VA: Validator<'v, A>, VB: Validator<'v, B>>(
+        &self,
+        keys: VA,
+        values: VB,
+    ) -> &Valid<'v, Self> {
+        self.assert_keys_valid(keys);
+        self.assert_values_valid(values);
+        Valid::new_ref(self)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_mut<'v, VA: Validator<'v, A>, VB: Validator<'v, B>>(
+        &mut self,
+        keys: VA,
+        values: VB,
+    ) -> &mut Valid<'v, Self> {
+        self.assert_keys_valid(keys);
+        self.assert_values_valid(values);
+        Valid::new_mut(self)
+    }
+}
+
+impl<A: Entity<IdType: DynamicIdType>, B: Entity<IdType = Static>> IdMultiMap<A, B> {
+    /// Validates a map whose values are static
+    #[inline]
+    #[track_caller]
+    pub fn validate_keys<'v, V: Validator<'v, A>>(&self, keys: V) -> &Valid<'v, Self> {
+        self.assert_keys_valid(keys);
+        Valid::new_ref(self)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_keys_mut<'v, V: Validator<'v, A>>(&mut self, keys: V) -> &mut Valid<'v, Self> {
+        self.assert_keys_valid(keys);
+        Valid::new_mut(self)
+    }
+}
+
+impl<A: Entity<IdType = Static>, B: Entity<IdType: DynamicIdType>> IdMultiMap<A, B> {
+    /// Validates a map whose keys are static
+    #[inline]
+    #[track_caller]
+    pub fn validate_values<'v, V: Validator<'v, B>>(&self, values: V) -> &Valid<'v, Self> {
+        self.assert_values_valid(values);
+        Valid::new_ref(self)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_values_mut<'v, V: Validator<'v, B>>(
+        &mut self,
+        values: V,
+    ) -> &mut Valid<'v, Self> {
+        self.assert_values_valid(values);
+        Valid::new_mut(self)
+    }
+}
+
+impl<'v, A: Entity, B: Entity> Valid<'v, IdMultiMap<A, B>> {
+    #[inline]
+    pub fn get_all<K: ValidId<Entity = A>>(
+        &self,
+        key: K,
+    ) -> impl Iterator<Item = Valid<'v, Id<B>>> + '_ {
+        self.value.get_all(key).iter().copied().map(Valid::new)
+    }
+
+    #[inline]
+    pub fn keys_of<V: ValidId<Entity = B>>(
+        &self,
+        value: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<A>>> + '_ {
+        self.value.keys_of(value).iter().copied().map(Valid::new)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Valid<'v, &Id<A>>, Valid<'v, &[Id<B>]>)> + '_ {
+        self.value
+            .iter()
+            .map(|(k, v)| (Valid::new(k), Valid::new(v)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Dyn, Dyn8, Stat};
+    use crate::Allocator;
+
+    #[test]
+    fn insert_and_remove_value() {
+        let key = Id::<Stat>::new(0, ());
+        let values = [Id::<Stat>::new(2, ()), Id::new(1, ())];
+        let mut map = IdMultiMap::<Stat, Stat>::default();
+
+        assert!(map.insert(key, values[0]));
+        assert!(map.insert(key, values[1]));
+        assert!(!map.insert(key, values[1]));
+        assert_eq!(&values, map.get_all(key));
+
+        assert!(map.remove_value(key, values[0]));
+        assert!(!map.remove_value(key, values[0]));
+        assert_eq!(&values[1..], map.get_all(key));
+        assert_eq!(&[key], map.keys_of(values[1]));
+
+        assert_eq!(vec![values[1]], map.remove(key));
+        assert!(map.is_empty());
+        assert!(map.keys_of(values[1]).is_empty());
+    }
+
+    #[test]
+    fn kill_key_removes_values() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let mut map = IdMultiMap::<Dyn, Dyn8>::default();
+
+        let key = a.create().value;
+        let value = b.create().value;
+        map.insert(Valid::new(key), Valid::new(value));
+
+        map.kill_key(Valid::new(key));
+        a.kill(key);
+
+        let map = map.validate(&a, &b);
+        assert!(map.keys_of(Valid::new(value)).next().is_none());
+    }
+
+    #[test]
+    fn kill_value_removes_from_keys() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let mut map = IdMultiMap::<Dyn, Dyn8>::default();
+
+        let key = Valid::new(a.create().value);
+        let values = b.create_many(2).value;
+        for value in &values {
+            map.insert(key, Valid::new(*value));
+        }
+
+        let killed = b.kill_many(&mut vec![values[0]]);
+        map.kill_many_values(&killed);
+
+        let map = map.validate(&a, &b);
+        assert_eq!(vec![values[1]], map.get_all(key).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "values are out of sync")]
+    fn validate_when_values_out_of_sync() {
+        let a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let map = IdMultiMap::<Dyn, Dyn8>::default();
+
+        let value = b.create().value;
+        b.kill(value);
+
+        map.validate(&a, &b);
+    }
+
+    #[test]
+    fn validate_static_keys() {
+        let mut b = Allocator::<Dyn>::default();
+        let mut map = IdMultiMap::<Stat, Dyn>::default();
+
+        let key = Id::<Stat>::new(0, ());
+        let value = b.create();
+        map.insert(key, value);
+
+        let value = value.value;
+        map.kill_value(Valid::new(value));
+        b.kill(value);
+
+        assert!(map.validate_values(&b).get_all(key).next().is_none());
+    }
+
+    #[test]
+    fn remap_values() {
+        let mut b = Allocator::<Dyn>::default();
+        let mut map = IdMultiMap::<Stat, Dyn>::default();
+
+        let key = Id::<Stat>::new(0, ());
+        let values = b.create_many(2).value;
+        map.insert(key, Valid::new(values[1]));
+
+        let killed = b.kill_many(&mut vec![values[0]]);
+        map.kill_many_values(&killed);
+
+        let remap = b.compact();
+        map.remap_values(&remap);
+
+        let moved = remap.get(values[1]);
+        assert_eq!(
+            vec![moved],
+            map.validate_values(&b).get_all(key).collect::<Vec<_>>()
+        );
+        assert_eq!(&[key], map.keys_of(Valid::new(moved)));
+    }
+}
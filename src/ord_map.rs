@@ -0,0 +1,283 @@
+use crate::allocator::{IdRemap, KillListener, KilledIds};
+use crate::gen::AllocGen;
+use crate::valid::{Synced, Validator};
+use crate::{DynamicIdType, Entity, Id, SyncError, Valid, ValidId};
+use std::collections::BTreeMap;
+
+/// An [`IdMap`](crate::IdMap) that iterates in Id order,
+/// so that iteration is deterministic between runs.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OrdIdMap<E: Entity, T> {
+    map: BTreeMap<Id<E>, T>,
+    gen: AllocGen<E>,
+}
+
+impl<E: Entity, T> Default for OrdIdMap<E, T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+            gen: Default::default(),
+        }
+    }
+}
+
+impl<E: Entity, T: Clone> Clone for OrdIdMap<E, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+            gen: self.gen.clone(),
+        }
+    }
+}
+
+impl<E: Entity, T: PartialEq> PartialEq for OrdIdMap<E, T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.map.eq(&other.map) && self.gen.eq(&other.gen)
+    }
+}
+
+impl<E: Entity, T: Eq> Eq for OrdIdMap<E, T> {}
+
+impl<E: Entity, T> OrdIdMap<E, T> {
+    #[inline]
+    pub fn entry<V: ValidId<Entity = E>>(
+        &mut self,
+        id: V,
+    ) -> std::collections::btree_map::Entry<'_, Id<E>, T> {
+        self.map.entry(id.id())
+    }
+
+    #[inline]
+    pub fn insert<V: ValidId<Entity = E>>(&mut self, id: V, value: T) -> Option<T> {
+        self.map.insert(id.id(), value)
+    }
+
+    #[inline]
+    pub fn remove<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<T> {
+        self.map.remove(&id.id())
+    }
+
+    #[inline]
+    pub fn get<V: ValidId<Entity = E>>(&self, id: V) -> Option<&T> {
+        self.map.get(&id.id())
+    }
+
+    #[inline]
+    pub fn get_mut<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<&mut T> {
+        self.map.get_mut(&id.id())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Iterates in Id order
+    #[inline]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Id<E>, &T)> + '_ {
+        self.map.iter()
+    }
+
+    /// Iterates in Id order
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = (&Id<E>, &mut T)> + '_ {
+        self.map.iter_mut()
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> OrdIdMap<E, T> {
+    #[inline]
+    pub fn kill<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<T> {
+        let id = id.id();
+        self.gen.increment(id);
+        self.map.remove(&id)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn kill_many(&mut self, killed: &KilledIds<E>) {
+        Synced::kill_many(self, killed);
+    }
+
+    /// Moves values to the new Ids from a compacted allocator
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        assert_eq!(&self.gen, remap.before());
+        for (old, new) in remap.moved() {
+            if let Some(value) = self.map.remove(old) {
+                self.map.insert(*new, value);
+            }
+        }
+        self.gen.clone_from(remap.after());
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate<'v, V: Validator<'v, E>>(&self, v: V) -> &Valid<'v, Self> {
+        Synced::validate(self, v)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_mut<'v, V: Validator<'v, E>>(&mut self, v: V) -> &mut Valid<'v, Self> {
+        Synced::validate_mut(self, v)
+    }
+
+    /// Returns how the collection is out of sync with the allocator instead of panicking
+    #[inline]
+    pub fn try_validate<'v, V: Validator<'v, E>>(
+        &self,
+        v: V,
+    ) -> Result<&Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate(self, v)
+    }
+
+    #[inline]
+    pub fn try_validate_mut<'v, V: Validator<'v, E>>(
+        &mut self,
+        v: V,
+    ) -> Result<&mut Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate_mut(self, v)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> Synced<E> for OrdIdMap<E, T> {
+    #[inline]
+    fn alloc_gen(&self) -> &AllocGen<E> {
+        &self.gen
+    }
+
+    #[inline]
+    fn kill_id(&mut self, id: Id<E>) {
+        self.kill(Valid::new(id));
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> KillListener<E> for OrdIdMap<E, T> {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        OrdIdMap::kill_many(self, killed);
+    }
+}
+
+impl<E: Entity, T, V: ValidId<Entity = E>> std::ops::Index<V> for OrdIdMap<E, T> {
+    type Output = T;
+    #[inline]
+    fn index(&self, index: V) -> &Self::Output {
+        &self.map[&index.id()]
+    }
+}
+
+impl<'v, E: Entity, T> Valid<'v, OrdIdMap<E, T>> {
+    /// Iterates in Id order
+    #[inline]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (Valid<'v, &Id<E>>, &T)> + '_ {
+        self.value.iter().map(|(k, v)| (Valid::new(k), v))
+    }
+
+    /// Iterates in Id order
+    #[inline]
+    pub fn iter_mut(
+        &mut self,
+    ) -> impl DoubleEndedIterator<Item = (Valid<'v, &Id<E>>, &mut T)> + '_ {
+        self.value.iter_mut().map(|(k, v)| (Valid::new(k), v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Dyn, Stat};
+    use crate::Allocator;
+
+    #[test]
+    fn iter_in_id_order() {
+        let mut map = OrdIdMap::<Stat, u32>::default();
+        for i in [5, 1, 3, 0] {
+            map.insert(Id::new(i, ()), i);
+        }
+
+        assert_eq!(
+            vec![0, 1, 3, 5],
+            map.iter().map(|(_, v)| *v).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn kill_many() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut map = OrdIdMap::<Dyn, usize>::default();
+
+        let ids = a.create_many(3).value;
+        for (i, id) in ids.iter().enumerate().rev() {
+            map.insert(Valid::new(*id), i);
+        }
+
+        let killed = a.kill_many(&mut vec![ids[1]]);
+        map.kill_many(&killed);
+
+        let map = map.validate(&a);
+        let values = map
+            .iter()
+            .map(|(id, v)| (*id.value, *v))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![(ids[0], 0), (ids[2], 2)], values);
+    }
+
+    #[test]
+    #[should_panic]
+    fn validate_when_out_of_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut map = OrdIdMap::<Dyn, ()>::default();
+
+        let id = a.create();
+        map.insert(id, ());
+
+        let id = id.value;
+        a.kill(id);
+
+        map.validate(&a);
+    }
+
+    #[test]
+    fn validate_mut_when_in_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut map = OrdIdMap::<Dyn, ()>::default();
+
+        let id = a.create();
+        map.insert(id, ());
+
+        map.kill(id);
+        let id = id.value;
+        a.kill(id);
+
+        map.validate_mut(&a);
+    }
+
+    #[test]
+    fn remap() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut map = OrdIdMap::<Dyn, u32>::default();
+
+        let ids = a.create_many(2).value;
+        map.insert(Valid::new(ids[1]), 1);
+
+        let killed = a.kill_many(&mut vec![ids[0]]);
+        map.kill_many(&killed);
+
+        let remap = a.compact();
+        map.remap(&remap);
+
+        let map = &map.validate(&a).value;
+        assert_eq!(Some(&1), map.get(Valid::new(remap.get(ids[1]))));
+        assert_eq!(None, map.get(Valid::new(ids[1])));
+    }
+}
@@ -0,0 +1,424 @@
+use crate::allocator::{KillListener, KilledIds};
+use crate::gen::AllocGen;
+use crate::relations::RelationError;
+use crate::valid::{Synced, Validator};
+use crate::{Allocator, DynamicIdType, Entity, Id, SyncError, Valid, ValidId};
+use fxhash::FxHashMap;
+use std::collections::VecDeque;
+
+/// A parent/child hierarchy of dynamic entities that can be rearranged at any time
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hierarchy<E: Entity> {
+    parents: FxHashMap<Id<E>, Id<E>>,
+    children: FxHashMap<Id<E>, Vec<Id<E>>>,
+    gen: AllocGen<E>,
+}
+
+impl<E: Entity> Default for Hierarchy<E> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            parents: Default::default(),
+            children: Default::default(),
+            gen: Default::default(),
+        }
+    }
+}
+
+impl<E: Entity> Clone for Hierarchy<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            parents: self.parents.clone(),
+            children: self.children.clone(),
+            gen: self.gen.clone(),
+        }
+    }
+}
+
+impl<E: Entity> Hierarchy<E> {
+    /// Moves the child under the parent, and returns its previous parent,
+    /// or an error if the parent is the child or one of its descendants
+    pub fn set_parent<C: ValidId<Entity = E>, P: ValidId<Entity = E>>(
+        &mut self,
+        child: C,
+        parent: P,
+    ) -> Result<Option<Id<E>>, RelationError<E>> {
+        let (child, parent) = (child.id(), parent.id());
+        if child == parent || self.ancestors_of(parent).any(|id| id == child) {
+            return Err(RelationError::Ancestor { id: child });
+        }
+
+        let previous = self.remove_parent(child);
+        self.parents.insert(child, parent);
+        self.children.entry(parent).or_default().push(child);
+        Ok(previous)
+    }
+
+    /// Removes the child from its parent, and returns the parent
+    #[inline]
+    pub fn detach<V: ValidId<Entity = E>>(&mut self, child: V) -> Option<Id<E>> {
+        self.remove_parent(child.id())
+    }
+
+    fn remove_parent(&mut self, child: Id<E>) -> Option<Id<E>> {
+        let parent = self.parents.remove(&child)?;
+        if let Some(children) = self.children.get_mut(&parent) {
+            children.retain(|id| *id != child);
+            if children.is_empty() {
+                self.children.remove(&parent);
+            }
+        }
+        Some(parent)
+    }
+
+    #[inline]
+    pub fn parent<V: ValidId<Entity = E>>(&self, child: V) -> Option<Id<E>> {
+        self.parents.get(&child.id()).copied()
+    }
+
+    /// The children of the parent, in the order they were added
+    #[inline]
+    pub fn children<V: ValidId<Entity = E>>(&self, parent: V) -> &[Id<E>] {
+        self.children_of(parent.id())
+    }
+
+    #[inline]
+    fn children_of(&self, parent: Id<E>) -> &[Id<E>] {
+        self.children.get(&parent).map_or(&[], Vec::as_slice)
+    }
+
+    /// The other children of the same parent
+    #[inline]
+    pub fn siblings<V: ValidId<Entity = E>>(&self, id: V) -> impl Iterator<Item = Id<E>> + '_ {
+        let id = id.id();
+        let siblings = match self.parents.get(&id) {
+            Some(parent) => self.children_of(*parent),
+            None => &[],
+        };
+        siblings.iter().copied().filter(move |s| *s != id)
+    }
+
+    /// The parent, grandparent, and so on up to the root
+    #[inline]
+    pub fn ancestors<V: ValidId<Entity = E>>(&self, id: V) -> impl Iterator<Item = Id<E>> + '_ {
+        self.ancestors_of(id.id())
+    }
+
+    #[inline]
+    fn ancestors_of(&self, id: Id<E>) -> impl Iterator<Item = Id<E>> + '_ {
+        std::iter::successors(self.parents.get(&id).copied(), |id| {
+            self.parents.get(id).copied()
+        })
+    }
+
+    /// The descendants of the Id in depth-first pre-order, not including the Id itself
+    #[inline]
+    pub fn depth_first<V: ValidId<Entity = E>>(&self, id: V) -> DepthFirst<'_, E> {
+        let mut stack = self.children_of(id.id()).to_vec();
+        stack.reverse();
+        DepthFirst {
+            hierarchy: self,
+            stack,
+        }
+    }
+
+    /// The descendants of the Id in breadth-first order, not including the Id itself
+    #[inline]
+    pub fn breadth_first<V: ValidId<Entity = E>>(&self, id: V) -> BreadthFirst<'_, E> {
+        BreadthFirst {
+            hierarchy: self,
+            queue: self.children_of(id.id()).iter().copied().collect(),
+        }
+    }
+
+    /// The number of Ids with a parent
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.parents.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.parents.is_empty()
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> Hierarchy<E> {
+    /// Removes the Id from the hierarchy, leaving its children without a parent
+    pub fn kill<V: ValidId<Entity = E>>(&mut self, id: V) {
+        let id = id.id();
+        self.gen.increment(id);
+        self.remove_parent(id);
+        for child in self.children.remove(&id).unwrap_or_default() {
+            self.parents.remove(&child);
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn kill_many(&mut self, killed: &KilledIds<E>) {
+        Synced::kill_many(self, killed);
+    }
+
+    /// Kills the Id and all of its descendants
+    #[track_caller]
+    pub fn despawn_recursive<V: ValidId<Entity = E>>(
+        &mut self,
+        alloc: &mut Allocator<E>,
+        id: V,
+    ) -> KilledIds<E> {
+        let mut ids = std::iter::once(id.id())
+            .chain(self.depth_first(id))
+            .collect::<Vec<_>>();
+
+        let killed = alloc.kill_many(&mut ids);
+        self.kill_many(&killed);
+        killed
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate<'v, V: Validator<'v, E>>(&self, v: V) -> &Valid<'v, Self> {
+        Synced::validate(self, v)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_mut<'v, V: Validator<'v, E>>(&mut self, v: V) -> &mut Valid<'v, Self> {
+        Synced::validate_mut(self, v)
+    }
+
+    /// Returns how the collection is out of sync with the allocator instead of panicking
+    #[inline]
+    pub fn try_validate<'v, V: Validator<'v, E>>(
+        &self,
+        v: V,
+    ) -> Result<&Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate(self, v)
+    }
+
+    #[inline]
+    pub fn try_validate_mut<'v, V: Validator<'v, E>>(
+        &mut self,
+        v: V,
+    ) -> Result<&mut Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate_mut(self, v)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> Synced<E> for Hierarchy<E> {
+    #[inline]
+    fn alloc_gen(&self) -> &AllocGen<E> {
+        &self.gen
+    }
+
+    #[inline]
+    fn kill_id(&mut self, id: Id<E>) {
+        self.kill(Valid::new(id));
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> KillListener<E> for Hierarchy<E> {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        Hierarchy::kill_many(self, killed);
+    }
+}
+
+impl<'v, E: Entity> Valid<'v, Hierarchy<E>> {
+    #[inline]
+    pub fn parent<V: ValidId<Entity = E>>(&self, child: V) -> Option<Valid<'v, Id<E>>> {
+        self.value.parent(child).map(Valid::new)
+    }
+
+    #[inline]
+    pub fn children<V: ValidId<Entity = E>>(
+        &self,
+        parent: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<E>>> + '_ {
+        self.value.children(parent).iter().copied().map(Valid::new)
+    }
+
+    #[inline]
+    pub fn siblings<V: ValidId<Entity = E>>(
+        &self,
+        id: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<E>>> + '_ {
+        self.value.siblings(id).map(Valid::new)
+    }
+
+    #[inline]
+    pub fn ancestors<V: ValidId<Entity = E>>(
+        &self,
+        id: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<E>>> + '_ {
+        self.value.ancestors(id).map(Valid::new)
+    }
+
+    #[inline]
+    pub fn depth_first<V: ValidId<Entity = E>>(
+        &self,
+        id: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<E>>> + '_ {
+        self.value.depth_first(id).map(Valid::new)
+    }
+
+    #[inline]
+    pub fn breadth_first<V: ValidId<Entity = E>>(
+        &self,
+        id: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<E>>> + '_ {
+        self.value.breadth_first(id).map(Valid::new)
+    }
+}
+
+/// `impl Iterator<Item = Id<E>>`
+#[derive(Debug)]
+pub struct DepthFirst<'a, E: Entity> {
+    hierarchy: &'a Hierarchy<E>,
+    stack: Vec<Id<E>>,
+}
+
+impl<E: Entity> Iterator for DepthFirst<'_, E> {
+    type Item = Id<E>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        let children = self.hierarchy.children_of(id);
+        self.stack.extend(children.iter().rev());
+        Some(id)
+    }
+}
+
+/// `impl Iterator<Item = Id<E>>`
+#[derive(Debug)]
+pub struct BreadthFirst<'a, E: Entity> {
+    hierarchy: &'a Hierarchy<E>,
+    queue: VecDeque<Id<E>>,
+}
+
+impl<E: Entity> Iterator for BreadthFirst<'_, E> {
+    type Item = Id<E>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.queue.pop_front()?;
+        let children = self.hierarchy.children_of(id);
+        self.queue.extend(children);
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Dyn;
+
+    #[test]
+    fn set_parent_moves_child() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut h = Hierarchy::<Dyn>::default();
+
+        let ids = a.create_many(3).value;
+        let id = |i: usize| Valid::new(ids[i]);
+
+        h.set_parent(id(2), id(0)).unwrap();
+        assert_eq!(Ok(Some(ids[0])), h.set_parent(id(2), id(1)));
+        h.set_parent(id(1), id(0)).unwrap();
+
+        assert!(h.children(id(0)).contains(&ids[1]));
+        assert!(!h.children(id(0)).contains(&ids[2]));
+        assert_eq!(&ids[2..3], h.children(id(1)));
+        assert_eq!(vec![ids[1], ids[0]], h.ancestors(id(2)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn set_parent_to_descendant() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut h = Hierarchy::<Dyn>::default();
+
+        let ids = a.create_many(3).value;
+        let id = |i: usize| Valid::new(ids[i]);
+        h.set_parent(id(1), id(0)).unwrap();
+        h.set_parent(id(2), id(1)).unwrap();
+
+        let error = RelationError::Ancestor { id: ids[0] };
+        assert_eq!(Err(error), h.set_parent(id(0), id(2)));
+        assert_eq!(
+            Err(RelationError::Ancestor { id: ids[2] }),
+            h.set_parent(id(2), id(2))
+        );
+        assert_eq!(None, h.parent(id(0)));
+    }
+
+    #[test]
+    fn detach_and_siblings() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut h = Hierarchy::<Dyn>::default();
+
+        let ids = a.create_many(3).value;
+        let id = |i: usize| Valid::new(ids[i]);
+        h.set_parent(id(1), id(0)).unwrap();
+        h.set_parent(id(2), id(0)).unwrap();
+
+        assert_eq!(vec![ids[2]], h.siblings(id(1)).collect::<Vec<_>>());
+        assert_eq!(Some(ids[0]), h.detach(id(2)));
+        assert_eq!(None, h.parent(id(2)));
+        assert_eq!(0, h.siblings(id(1)).count());
+    }
+
+    #[test]
+    fn traversal_order() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut h = Hierarchy::<Dyn>::default();
+
+        let ids = a.create_many(4).value;
+        let id = |i: usize| Valid::new(ids[i]);
+        h.set_parent(id(1), id(0)).unwrap();
+        h.set_parent(id(2), id(0)).unwrap();
+        h.set_parent(id(3), id(1)).unwrap();
+
+        let dfs = h.depth_first(id(0)).collect::<Vec<_>>();
+        let bfs = h.breadth_first(id(0)).collect::<Vec<_>>();
+
+        assert_eq!(vec![ids[1], ids[3], ids[2]], dfs);
+        assert_eq!(vec![ids[1], ids[2], ids[3]], bfs);
+    }
+
+    #[test]
+    fn despawn_recursive() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut h = Hierarchy::<Dyn>::default();
+
+        let ids = a.create_many(4).value;
+        let id = |i: usize| Valid::new(ids[i]);
+        h.set_parent(id(1), id(0)).unwrap();
+        h.set_parent(id(2), id(0)).unwrap();
+        h.set_parent(id(3), id(1)).unwrap();
+
+        let killed = h.despawn_recursive(&mut a, id(1));
+        assert_eq!(&[ids[1], ids[3]], killed.ids());
+
+        let h = h.validate(&a);
+        assert!(!a.is_alive(ids[3]));
+        assert_eq!(
+            vec![ids[2]],
+            h.children(Valid::new(ids[0])).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn validate_when_out_of_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let h = Hierarchy::<Dyn>::default();
+
+        let id = a.create().value;
+        a.kill(id);
+
+        h.validate(&a);
+    }
+}
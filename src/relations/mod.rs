@@ -1,3 +1,9 @@
+pub use hierarchy::*;
 pub use range_relation::*;
+pub use range_tree::*;
+pub use relation::*;
 
+pub mod hierarchy;
 pub mod range_relation;
+pub mod range_tree;
+pub mod relation;
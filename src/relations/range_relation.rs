@@ -1,13 +1,18 @@
-use crate::component::RawComponent;
+use crate::component::{Component, RawComponent};
 use crate::{Entity, Id, IdRange, Static, ValidId};
 use iter_context::ContextualIterator;
+use std::collections::VecDeque;
 use std::ops::Index;
 
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RangeRelation<E: Entity> {
+    /// Has been unlinked from its parent or children
+    Unlinked,
     ChildOf(Id<E>),
     ParentOf(IdRange<E>),
+    /// Both a child of the parent and a parent of the range, e.g., a planet with moons
+    ChildAndParentOf(Id<E>, IdRange<E>),
 }
 
 impl<E: Entity> Clone for RangeRelation<E> {
@@ -25,6 +30,10 @@ impl<E: Entity> PartialEq for RangeRelation<E> {
         match (self, other) {
             (Self::ChildOf(l0), Self::ChildOf(r0)) => l0 == r0,
             (Self::ParentOf(l0), Self::ParentOf(r0)) => l0 == r0,
+            (Self::ChildAndParentOf(l0, l1), Self::ChildAndParentOf(r0, r1)) => {
+                l0 == r0 && l1 == r1
+            }
+            (Self::Unlinked, Self::Unlinked) => true,
             _ => false,
         }
     }
@@ -36,33 +45,124 @@ unsafe impl<E: Entity> Send for RangeRelation<E> {}
 unsafe impl<E: Entity> Sync for RangeRelation<E> {}
 
 impl<E: Entity> RangeRelation<E> {
+    #[inline]
+    pub fn new(parent: Option<Id<E>>, children: Option<IdRange<E>>) -> Self {
+        match (parent, children) {
+            (None, None) => RangeRelation::Unlinked,
+            (Some(p), None) => RangeRelation::ChildOf(p),
+            (None, Some(c)) => RangeRelation::ParentOf(c),
+            (Some(p), Some(c)) => RangeRelation::ChildAndParentOf(p, c),
+        }
+    }
+
     #[inline]
     pub fn parent_of(self) -> Option<IdRange<E>> {
         match self {
-            RangeRelation::ParentOf(c) => Some(c),
-            RangeRelation::ChildOf(_) => None,
+            RangeRelation::ParentOf(c) | RangeRelation::ChildAndParentOf(_, c) => Some(c),
+            RangeRelation::ChildOf(_) | RangeRelation::Unlinked => None,
         }
     }
 
     #[inline]
     pub fn child_of(self) -> Option<Id<E>> {
         match self {
-            RangeRelation::ChildOf(p) => Some(p),
-            RangeRelation::ParentOf(_) => None,
+            RangeRelation::ChildOf(p) | RangeRelation::ChildAndParentOf(p, _) => Some(p),
+            RangeRelation::ParentOf(_) | RangeRelation::Unlinked => None,
         }
     }
 
     #[inline]
     pub fn is_parent(&self) -> bool {
-        matches!(self, Self::ParentOf(_))
+        matches!(self, Self::ParentOf(_) | Self::ChildAndParentOf(..))
     }
 
     #[inline]
     pub fn is_child(&self) -> bool {
-        matches!(self, Self::ChildOf(_))
+        matches!(self, Self::ChildOf(_) | Self::ChildAndParentOf(..))
+    }
+
+    #[inline]
+    pub fn is_unlinked(&self) -> bool {
+        matches!(self, Self::Unlinked)
+    }
+}
+
+/// Describes why a [`RangeRelations`] or [`Hierarchy`](super::Hierarchy) could not be changed
+#[derive(Debug)]
+pub enum RelationError<E: Entity> {
+    /// The Id is already linked by the given relation
+    Linked {
+        id: Id<E>,
+        relation: RangeRelation<E>,
+    },
+    /// The Id is not a parent, and has the given relation instead
+    NotParent {
+        id: Id<E>,
+        relation: Option<RangeRelation<E>>,
+    },
+    /// The Id is the parent or one of its ancestors, so cannot be its child
+    Ancestor { id: Id<E> },
+}
+
+impl<E: Entity> Clone for RelationError<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E: Entity> Copy for RelationError<E> {}
+
+impl<E: Entity> PartialEq for RelationError<E> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Self::Linked {
+                    id: l0,
+                    relation: l1,
+                },
+                Self::Linked {
+                    id: r0,
+                    relation: r1,
+                },
+            ) => l0 == r0 && l1 == r1,
+            (
+                Self::NotParent {
+                    id: l0,
+                    relation: l1,
+                },
+                Self::NotParent {
+                    id: r0,
+                    relation: r1,
+                },
+            ) => l0 == r0 && l1 == r1,
+            (Self::Ancestor { id: l0 }, Self::Ancestor { id: r0 }) => l0 == r0,
+            _ => false,
+        }
+    }
+}
+
+impl<E: Entity> Eq for RelationError<E> {}
+
+impl<E: Entity> std::fmt::Display for RelationError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RelationError::Linked { id, relation } => {
+                write!(f, "id {} is already linked: {relation:?}", id.index())
+            }
+            RelationError::NotParent { id, relation } => {
+                write!(f, "id {} is not a parent: {relation:?}", id.index())
+            }
+            RelationError::Ancestor { id } => {
+                write!(f, "id {} cannot be a child of its descendant", id.index())
+            }
+        }
     }
 }
 
+impl<E: Entity> std::error::Error for RelationError<E> {}
+
 #[derive(Debug)]
 pub struct RangeRelations<E: Entity> {
     values: RawComponent<E, RangeRelation<E>>,
@@ -90,26 +190,184 @@ impl<E: Entity> Clone for RangeRelations<E> {
     }
 }
 
-/// Requires fixed because unlinking is not implemented
+/// Requires fixed because children must be contiguous
 impl<E: Entity<IdType = Static>> RangeRelations<E> {
+    #[inline]
+    fn relation(&self, id: Id<E>) -> RangeRelation<E> {
+        self.values
+            .get(id)
+            .copied()
+            .unwrap_or(RangeRelation::Unlinked)
+    }
+
+    /// Returns an error if the Id already has a parent, other than the given one
+    #[inline]
+    fn check_no_parent(&self, id: Id<E>, parent: Option<Id<E>>) -> Result<(), RelationError<E>> {
+        let relation = self.relation(id);
+        match relation.child_of() {
+            Some(p) if Some(p) != parent => Err(RelationError::Linked { id, relation }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns an error if the parent or any of its ancestors would become its own descendant
+    #[inline]
+    fn check_no_cycle(&self, parent: Id<E>, children: IdRange<E>) -> Result<(), RelationError<E>> {
+        match std::iter::once(parent)
+            .chain(self.ancestors(parent))
+            .find(|id| children.contains(*id))
+        {
+            Some(id) => Err(RelationError::Ancestor { id }),
+            None => Ok(()),
+        }
+    }
+
+    /// Sets the relation, leaving any skipped Ids unlinked
+    #[inline]
+    #[track_caller]
+    pub(crate) fn insert_filled(&mut self, id: Id<E>, relation: RangeRelation<E>) {
+        self.values
+            .insert_with(id, relation, || RangeRelation::Unlinked);
+    }
+
+    #[inline]
+    #[track_caller]
+    fn set_parent(&mut self, id: Id<E>, parent: Option<Id<E>>) {
+        let children = self.relation(id).parent_of();
+        self.insert_filled(id, RangeRelation::new(parent, children));
+    }
+
     #[inline]
     #[track_caller]
-    fn insert_if_empty(&mut self, id: impl ValidId<Entity = E>, relation: RangeRelation<E>) {
-        match self.values.get(id.id()) {
-            None => self.values.insert(id.id(), relation),
-            Some(_existing) => panic!(
-                "{}::insert_if_empty: cannot insert over existing relation",
-                std::any::type_name::<Self>()
-            ),
+    fn set_children(&mut self, id: Id<E>, children: Option<IdRange<E>>) {
+        let parent = self.relation(id).child_of();
+        self.insert_filled(id, RangeRelation::new(parent, children));
+    }
+
+    /// Links the parent to the children,
+    /// or returns the existing relation of the first Id that is already linked
+    ///
+    /// The parent may already be a child, and the children may already be parents
+    #[inline]
+    #[track_caller]
+    pub fn link(&mut self, parent: Id<E>, children: IdRange<E>) -> Result<(), RelationError<E>> {
+        let relation = self.relation(parent);
+        if relation.is_parent() {
+            return Err(RelationError::Linked {
+                id: parent,
+                relation,
+            });
+        }
+        self.check_no_cycle(parent, children)?;
+        for child in children {
+            self.check_no_parent(child, None)?;
+        }
+
+        self.set_children(parent, Some(children));
+        for child in children {
+            self.set_parent(child, Some(parent));
+        }
+        Ok(())
+    }
+
+    /// Clears the children of the parent, and returns them
+    ///
+    /// The parent keeps its own parent, and the children keep their own children
+    #[inline]
+    pub fn unlink(&mut self, parent: Id<E>) -> Result<IdRange<E>, RelationError<E>> {
+        let children = self.children_of(parent)?;
+
+        self.set_children(parent, None);
+        for child in children {
+            self.set_parent(child, None);
         }
+        Ok(children)
     }
 
+    /// Replaces the children of the parent, and returns the previous children
+    ///
+    /// The new children must not have a parent, or already be children of the parent
     #[inline]
     #[track_caller]
-    pub fn link(&mut self, parent: Id<E>, children: IdRange<E>) {
-        self.insert_if_empty(parent, RangeRelation::ParentOf(children));
+    pub fn relink(
+        &mut self,
+        parent: Id<E>,
+        children: IdRange<E>,
+    ) -> Result<IdRange<E>, RelationError<E>> {
+        let previous = self.children_of(parent)?;
+        self.check_no_cycle(parent, children)?;
+        for child in children {
+            self.check_no_parent(child, Some(parent))?;
+        }
+
+        for child in previous {
+            self.set_parent(child, None);
+        }
+        self.set_children(parent, Some(children));
         for child in children {
-            self.insert_if_empty(child, RangeRelation::ChildOf(parent));
+            self.set_parent(child, Some(parent));
+        }
+        Ok(previous)
+    }
+
+    #[inline]
+    fn children_of(&self, parent: Id<E>) -> Result<IdRange<E>, RelationError<E>> {
+        match self.relation(parent).parent_of() {
+            Some(children) => Ok(children),
+            None => Err(RelationError::NotParent {
+                id: parent,
+                relation: self.values.get(parent).copied(),
+            }),
+        }
+    }
+
+    /// The parent, grandparent, and so on up to the root
+    #[inline]
+    pub fn ancestors(&self, id: Id<E>) -> impl Iterator<Item = Id<E>> + '_ {
+        std::iter::successors(self.relation(id).child_of(), |p| {
+            self.relation(*p).child_of()
+        })
+    }
+
+    /// The Id at the top of the hierarchy, which is the Id itself if it has no parent
+    #[inline]
+    pub fn root_of(&self, id: Id<E>) -> Id<E> {
+        self.ancestors(id).last().unwrap_or(id)
+    }
+
+    /// The children, grandchildren, and so on in breadth-first order
+    #[inline]
+    pub fn descendants(&self, id: Id<E>) -> Descendants<'_, E> {
+        Descendants {
+            relations: self,
+            current: IdRange::default().into_iter(),
+            queue: self.relation(id).parent_of().into_iter().collect(),
+        }
+    }
+
+    /// The descendants as a single range,
+    /// or `None` if they were not allocated contiguously
+    #[inline]
+    pub fn descendant_range(&self, id: Id<E>) -> Option<IdRange<E>> {
+        let mut count = 0;
+        let mut bounds = None::<std::ops::Range<usize>>;
+        for range in self.descendants(id).ranges() {
+            let range = range.range_usize();
+            if range.is_empty() {
+                continue;
+            }
+            count += range.len();
+            let bounds = bounds.get_or_insert(range.clone());
+            bounds.start = bounds.start.min(range.start);
+            bounds.end = bounds.end.max(range.end);
+        }
+
+        match bounds {
+            None => Some(IdRange::default()),
+            Some(bounds) if bounds.len() == count => {
+                Some(IdRange::new(bounds.start as u32, bounds.end as u32))
+            }
+            Some(_) => None,
         }
     }
 
@@ -119,7 +377,109 @@ impl<E: Entity<IdType = Static>> RangeRelations<E> {
         iter: I,
     ) -> impl Iterator<Item = Id<E>> + 'a {
         iter.into_iter()
-            .filter(move |id| matches!(self[id], RangeRelation::ParentOf(_)))
+            .filter(move |id| self.relation(*id).is_parent())
+    }
+
+    /// Folds the values of each parent's children into the parent's value in `output`,
+    /// e.g., summing the masses of a planet's moons.
+    ///
+    /// Only direct children are folded, and entities without children are left unchanged.
+    #[track_caller]
+    pub fn reduce<T, U, F: Fn(&[T]) -> U>(
+        &self,
+        values: &Component<E, T>,
+        output: &mut Component<E, U>,
+        reduce: F,
+    ) {
+        self.assert_covered_by("values", values.len());
+        self.assert_covered_by("output", output.len());
+        for (i, relation) in self.values.values.iter().enumerate() {
+            if let Some(children) = relation.parent_of() {
+                output[Id::new(i as u32, ())] = reduce(&values[children]);
+            }
+        }
+    }
+
+    /// Pushes the value of each child's parent down into the child's value in `output`,
+    /// e.g., adding a planet's position to the relative positions of its moons.
+    ///
+    /// Only one level is covered, since parent values are read from `values` rather than
+    /// `output`, so a moon does not receive the position of its planet's star.
+    /// Entities without a parent are left unchanged.
+    #[track_caller]
+    pub fn scatter<T, U, F: Fn(&T, &mut U)>(
+        &self,
+        values: &Component<E, T>,
+        output: &mut Component<E, U>,
+        scatter: F,
+    ) {
+        self.assert_covered_by("values", values.len());
+        self.assert_covered_by("output", output.len());
+        for (i, relation) in self.values.values.iter().enumerate() {
+            if let Some(parent) = relation.child_of() {
+                scatter(&values[parent], &mut output[Id::new(i as u32, ())]);
+            }
+        }
+    }
+
+    /// Runs [`reduce`](Self::reduce) in parallel over the parents
+    #[cfg(feature = "rayon")]
+    #[track_caller]
+    pub fn par_reduce<T: Sync, U: Send, F: Fn(&[T]) -> U + Sync>(
+        &self,
+        values: &Component<E, T>,
+        output: &mut Component<E, U>,
+        reduce: F,
+    ) where
+        E: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.assert_covered_by("values", values.len());
+        self.assert_covered_by("output", output.len());
+        (&mut *output)
+            .par_iter_mut()
+            .zip(self.values.values.par_iter())
+            .for_each(|(output, relation)| {
+                if let Some(children) = relation.parent_of() {
+                    *output = reduce(&values[children]);
+                }
+            });
+    }
+
+    /// Runs [`scatter`](Self::scatter) in parallel over the children
+    #[cfg(feature = "rayon")]
+    #[track_caller]
+    pub fn par_scatter<T: Sync, U: Send, F: Fn(&T, &mut U) + Sync>(
+        &self,
+        values: &Component<E, T>,
+        output: &mut Component<E, U>,
+        scatter: F,
+    ) where
+        E: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.assert_covered_by("values", values.len());
+        self.assert_covered_by("output", output.len());
+        (&mut *output)
+            .par_iter_mut()
+            .zip(self.values.values.par_iter())
+            .for_each(|(output, relation)| {
+                if let Some(parent) = relation.child_of() {
+                    scatter(&values[parent], output);
+                }
+            });
+    }
+
+    #[inline]
+    #[track_caller]
+    fn assert_covered_by(&self, name: &str, len: usize) {
+        assert!(
+            len >= self.values.len(),
+            "{name} has {len} values but the relations have {}",
+            self.values.len()
+        );
     }
 }
 
@@ -146,6 +506,48 @@ impl<E: Entity> ContextualIterator for &RangeRelations<E> {
     type Context = E;
 }
 
+/// `impl Iterator<Item = Id<E>>`
+#[derive(Debug)]
+pub struct Descendants<'a, E: Entity<IdType = Static>> {
+    relations: &'a RangeRelations<E>,
+    current: <IdRange<E> as IntoIterator>::IntoIter,
+    queue: VecDeque<IdRange<E>>,
+}
+
+impl<'a, E: Entity<IdType = Static>> Descendants<'a, E> {
+    /// The ranges of children in breadth-first order
+    fn ranges(self) -> impl Iterator<Item = IdRange<E>> + 'a {
+        let relations = self.relations;
+        let mut queue = self.queue;
+        std::iter::from_fn(move || {
+            let range = queue.pop_front()?;
+            queue.extend(
+                range
+                    .into_iter()
+                    .filter_map(|id| relations.relation(id).parent_of()),
+            );
+            Some(range)
+        })
+    }
+}
+
+impl<E: Entity<IdType = Static>> Iterator for Descendants<'_, E> {
+    type Item = Id<E>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(id) = self.current.next() {
+                if let Some(children) = self.relations.relation(id).parent_of() {
+                    self.queue.push_back(children);
+                }
+                return Some(id);
+            }
+            self.current = self.queue.pop_front()?.into_iter();
+        }
+    }
+}
+
 #[cfg(feature = "rayon")]
 impl<'a, E: Entity + 'a> rayon::iter::IntoParallelRefIterator<'a> for &'a RangeRelations<E> {
     type Iter = rayon::slice::Iter<'a, RangeRelation<E>>;
@@ -186,7 +588,7 @@ mod test {
         let mut graph = RangeRelations::<Stat>::default();
         let parent = Id::<Stat>::new(0, ());
 
-        graph.link(parent, IdRange::default());
+        graph.link(parent, IdRange::default()).unwrap();
 
         assert_eq!(graph[parent], RangeRelation::ParentOf(IdRange::default()));
     }
@@ -198,14 +600,13 @@ mod test {
         let id0 = Id::new(0, ());
         let id1 = Id::new(1, ());
 
-        graph.link(id0, IdRange::from(id1));
+        graph.link(id0, IdRange::from(id1)).unwrap();
 
         assert_eq!(graph[id0], RangeRelation::ParentOf(IdRange::from(id1.id())));
         assert_eq!(graph[id1], RangeRelation::ChildOf(id0.id()));
     }
 
     #[test]
-    #[should_panic]
     fn link_child_to_another_child() {
         let mut graph = RangeRelations::<Stat>::default();
 
@@ -213,38 +614,286 @@ mod test {
         let children = IdRange::new(1, 2);
         let id2 = Id::<Stat>::new(2, ());
 
-        graph.link(id0, children);
-        graph.link(id2, children);
+        graph.link(id0, children).unwrap();
+        let error = graph.link(id2, children).unwrap_err();
+
+        assert_eq!(
+            RelationError::Linked {
+                id: Id::new(1, ()),
+                relation: RangeRelation::ChildOf(id0),
+            },
+            error
+        );
     }
 
     #[test]
-    #[should_panic]
     fn insert_parent_overtop_of_another_link() {
         let mut graph = RangeRelations::<Stat>::default();
 
         let id0 = Id::<Stat>::new(0, ());
 
-        graph.link(id0, IdRange::default());
-        graph.link(id0, IdRange::default());
+        graph.link(id0, IdRange::default()).unwrap();
+        let error = graph.link(id0, IdRange::default()).unwrap_err();
+
+        assert_eq!(
+            RelationError::Linked {
+                id: id0,
+                relation: RangeRelation::ParentOf(IdRange::default()),
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn link_parent_as_child_of_another_parent() {
+        let mut graph = RangeRelations::<Stat>::default();
+
+        let id0 = Id::<Stat>::new(0, ());
+        let id1 = Id::<Stat>::new(1, ());
+
+        graph.link(id0, IdRange::default()).unwrap();
+        graph.link(id1, IdRange::new(0, 1)).unwrap();
+
+        assert_eq!(
+            RangeRelation::ChildAndParentOf(id1, IdRange::default()),
+            graph[id0]
+        );
+        assert_eq!(RangeRelation::ParentOf(IdRange::new(0, 1)), graph[id1]);
     }
 
     #[test]
-    #[should_panic]
-    fn insert_child_overtop_of_another_parent() {
+    fn link_ancestor_as_child() {
         let mut graph = RangeRelations::<Stat>::default();
 
         let id0 = Id::<Stat>::new(0, ());
         let id1 = Id::<Stat>::new(1, ());
 
-        graph.link(id0, IdRange::default());
-        graph.link(id1, IdRange::new(0, 1));
+        graph.link(id0, id1.into()).unwrap();
+        graph.link(id1, IdRange::new(2, 3)).unwrap();
+
+        assert_eq!(
+            Err(RelationError::Ancestor { id: id0 }),
+            graph.link(Id::new(2, ()), IdRange::new(0, 1))
+        );
+    }
+
+    #[test]
+    fn link_parent_as_own_child() {
+        let mut graph = RangeRelations::<Stat>::default();
+
+        let id0 = Id::<Stat>::new(0, ());
+
+        assert!(graph.link(id0, IdRange::new(0, 1)).is_err());
     }
 
     #[test]
-    #[should_panic]
     fn link_after_skipping_first_id() {
         let mut graph = RangeRelations::<Stat>::default();
+        let id = |i: u32| Id::<Stat>::new(i, ());
+
+        graph.link(id(1), id(2).into()).unwrap();
+
+        assert!(graph[id(0)].is_unlinked());
+        assert_eq!(RangeRelation::ParentOf(id(2).into()), graph[id(1)]);
+        assert_eq!(RangeRelation::ChildOf(id(1)), graph[id(2)]);
+    }
+
+    #[test]
+    fn unlink_clears_parent_and_children() {
+        let mut graph = RangeRelations::<Stat>::default();
+
+        let id0 = Id::<Stat>::new(0, ());
+        let children = IdRange::new(1, 3);
+
+        graph.link(id0, children).unwrap();
+        assert_eq!(Ok(children), graph.unlink(id0));
+
+        assert!(graph[id0].is_unlinked());
+        assert!(children.into_iter().all(|id| graph[id].is_unlinked()));
+
+        graph.link(Id::new(2, ()), IdRange::new(0, 2)).unwrap();
+    }
+
+    #[test]
+    fn unlink_child() {
+        let mut graph = RangeRelations::<Stat>::default();
+
+        let id0 = Id::<Stat>::new(0, ());
+        let id1 = Id::<Stat>::new(1, ());
+
+        graph.link(id0, id1.into()).unwrap();
+
+        assert_eq!(
+            Err(RelationError::NotParent {
+                id: id1,
+                relation: Some(RangeRelation::ChildOf(id0)),
+            }),
+            graph.unlink(id1)
+        );
+    }
+
+    #[test]
+    fn relink_replaces_children() {
+        let mut graph = RangeRelations::<Stat>::default();
+
+        let id0 = Id::<Stat>::new(0, ());
+        let id1 = Id::<Stat>::new(1, ());
+        graph.link(id0, IdRange::new(1, 3)).unwrap();
+        graph.link(Id::new(3, ()), IdRange::new(4, 5)).unwrap();
+
+        assert!(graph.relink(id0, IdRange::new(3, 5)).is_err());
+        assert_eq!(
+            Ok(IdRange::new(1, 3)),
+            graph.relink(id0, IdRange::new(2, 3))
+        );
+
+        assert!(graph[id1].is_unlinked());
+        assert_eq!(RangeRelation::ChildOf(id0), graph[Id::new(2, ())]);
+        assert_eq!(RangeRelation::ParentOf(IdRange::new(2, 3)), graph[id0]);
+    }
+
+    #[test]
+    fn parents_past_the_end() {
+        let mut graph = RangeRelations::<Stat>::default();
+        let id = |i: u32| Id::<Stat>::new(i, ());
+
+        graph.link(id(0), IdRange::new(1, 3)).unwrap();
+        graph.link(id(2), IdRange::new(3, 4)).unwrap();
+
+        let ids = (0..6).map(id);
+        assert_eq!(vec![id(0), id(2)], graph.parents(ids).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn ancestors_and_root_of() {
+        let mut graph = RangeRelations::<Stat>::default();
+        let id = |i: u32| Id::<Stat>::new(i, ());
+
+        graph.link(id(0), IdRange::new(1, 3)).unwrap();
+        graph.link(id(3), IdRange::new(4, 5)).unwrap();
+        graph.link(id(2), IdRange::new(5, 7)).unwrap();
+        graph.link(id(4), IdRange::new(7, 8)).unwrap();
+
+        assert_eq!(
+            vec![id(2), id(0)],
+            graph.ancestors(id(6)).collect::<Vec<_>>()
+        );
+        assert_eq!(id(0), graph.root_of(id(6)));
+        assert_eq!(id(3), graph.root_of(id(7)));
+        assert_eq!(id(0), graph.root_of(id(0)));
+    }
+
+    #[test]
+    fn descendants_breadth_first() {
+        let mut graph = RangeRelations::<Stat>::default();
+        let id = |i: u32| Id::<Stat>::new(i, ());
+
+        graph.link(id(0), IdRange::new(1, 3)).unwrap();
+        graph.link(id(2), IdRange::new(5, 7)).unwrap();
+
+        assert_eq!(
+            vec![id(1), id(2), id(5), id(6)],
+            graph.descendants(id(0)).collect::<Vec<_>>()
+        );
+        assert_eq!(0, graph.descendants(id(1)).count());
+    }
+
+    #[test]
+    fn descendant_range() {
+        let mut graph = RangeRelations::<Stat>::default();
+        let id = |i: u32| Id::<Stat>::new(i, ());
+
+        graph.link(id(0), IdRange::new(1, 3)).unwrap();
+        graph.link(id(3), IdRange::new(4, 5)).unwrap();
+        graph.link(id(2), IdRange::new(5, 7)).unwrap();
+        graph.link(id(4), IdRange::new(7, 8)).unwrap();
+
+        assert_eq!(None, graph.descendant_range(id(0)));
+        assert_eq!(None, graph.descendant_range(id(3)));
+        assert_eq!(Some(IdRange::new(5, 7)), graph.descendant_range(id(2)));
+        assert_eq!(Some(IdRange::default()), graph.descendant_range(id(1)));
+
+        let mut graph = RangeRelations::<Stat>::default();
+        graph.link(id(0), IdRange::new(1, 3)).unwrap();
+        graph.link(id(1), IdRange::new(3, 4)).unwrap();
+
+        assert_eq!(Some(IdRange::new(1, 4)), graph.descendant_range(id(0)));
+    }
+
+    #[test]
+    fn reduce_children_into_parents() {
+        // a star with two planets, and a moon of the first planet
+        let mut graph = RangeRelations::<Stat>::default();
+        graph.link(Id::new(0, ()), IdRange::new(1, 3)).unwrap();
+        graph.link(Id::new(1, ()), IdRange::new(3, 4)).unwrap();
+
+        let mass = Component::<Stat, u32>::from(vec![1000, 10, 20, 1]);
+        let mut child_mass = Component::<Stat, u32>::from(vec![0; 4]);
+
+        graph.reduce(&mass, &mut child_mass, |children| children.iter().sum());
+
+        assert_eq!(Component::from(vec![30, 1, 0, 0]), child_mass);
+    }
+
+    #[test]
+    fn scatter_parents_into_children_one_level() {
+        // a star with two planets, and a moon of the first planet
+        let mut graph = RangeRelations::<Stat>::default();
+        graph.link(Id::new(0, ()), IdRange::new(1, 3)).unwrap();
+        graph.link(Id::new(1, ()), IdRange::new(3, 4)).unwrap();
+
+        let relative = Component::<Stat, u32>::from(vec![100, 10, 20, 1]);
+        let mut offset = relative.clone();
+
+        graph.scatter(&relative, &mut offset, |parent, child| *child += parent);
+
+        assert_eq!(Component::from(vec![100, 110, 120, 11]), offset);
+    }
+
+    #[test]
+    #[should_panic(expected = "output has 2 values")]
+    fn reduce_into_short_output() {
+        // a star with two planets, and a moon of the first planet
+        let mut graph = RangeRelations::<Stat>::default();
+        graph.link(Id::new(0, ()), IdRange::new(1, 3)).unwrap();
+        graph.link(Id::new(1, ()), IdRange::new(3, 4)).unwrap();
+
+        let mass = Component::<Stat, u32>::from(vec![1000, 10, 20, 1]);
+        let mut child_mass = Component::<Stat, u32>::from(vec![0; 2]);
+
+        graph.reduce(&mass, &mut child_mass, |children| children.iter().sum());
+    }
+
+    #[test]
+    #[should_panic(expected = "values has 2 values")]
+    fn reduce_from_short_values() {
+        let mut graph = RangeRelations::<Stat>::default();
+        graph.link(Id::new(0, ()), IdRange::new(1, 3)).unwrap();
+
+        let mass = Component::<Stat, u32>::from(vec![1000, 10]);
+        let mut child_mass = Component::<Stat, u32>::from(vec![0; 3]);
+
+        graph.reduce(&mass, &mut child_mass, |children| children.iter().sum());
+    }
+
+    #[test]
+    #[cfg(feature = "rayon")]
+    fn par_reduce_and_scatter_match_serial() {
+        // a star with two planets, and a moon of the first planet
+        let mut graph = RangeRelations::<Stat>::default();
+        graph.link(Id::new(0, ()), IdRange::new(1, 3)).unwrap();
+        graph.link(Id::new(1, ()), IdRange::new(3, 4)).unwrap();
+
+        let values = Component::<Stat, u32>::from(vec![1000, 10, 20, 1]);
+
+        let mut serial = values.clone();
+        let mut parallel = values.clone();
+        graph.reduce(&values, &mut serial, |children| children.iter().sum());
+        graph.par_reduce(&values, &mut parallel, |children| children.iter().sum());
+        assert_eq!(serial, parallel);
 
-        graph.link(Id::<Stat>::new(1, ()), Id::<Stat>::new(2, ()).into());
+        graph.scatter(&values, &mut serial, |parent, child| *child += parent);
+        graph.par_scatter(&values, &mut parallel, |parent, child| *child += parent);
+        assert_eq!(serial, parallel);
     }
 }
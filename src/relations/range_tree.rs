@@ -0,0 +1,191 @@
+use crate::relations::{RangeRelation, RangeRelations};
+use crate::{Entity, Id, IdRange, RangeAllocator, Static};
+use std::collections::VecDeque;
+
+/// A nested description of a hierarchy, to be allocated by [`RangeRelations::create_tree`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeTree<T> {
+    pub value: T,
+    pub children: Vec<RangeTree<T>>,
+}
+
+impl<T> RangeTree<T> {
+    #[inline]
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            children: vec![],
+        }
+    }
+
+    #[inline]
+    pub fn with_child(mut self, child: RangeTree<T>) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    #[inline]
+    pub fn with_children<I: IntoIterator<Item = RangeTree<T>>>(mut self, children: I) -> Self {
+        self.children.extend(children);
+        self
+    }
+
+    /// The number of nodes in the tree, including the root
+    #[inline]
+    pub fn node_count(&self) -> usize {
+        1 + self
+            .children
+            .iter()
+            .map(RangeTree::node_count)
+            .sum::<usize>()
+    }
+}
+
+/// The order in which the nodes of a [`RangeTree`] are allocated.
+///
+/// The children of each node are always allocated as one contiguous range.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TreeLayout {
+    /// Each subtree is allocated contiguously, so the descendants of a node form one range
+    #[default]
+    DepthFirst,
+    /// Each level of the tree is allocated contiguously
+    BreadthFirst,
+}
+
+impl<E: Entity<IdType = Static>> RangeRelations<E> {
+    /// Allocates every node of the tree and links each node to its children.
+    ///
+    /// Returns the Id of each node with its value, in Id order,
+    /// so that the values can be inserted into components.
+    pub fn create_tree<T>(
+        &mut self,
+        alloc: &mut RangeAllocator<E>,
+        tree: RangeTree<T>,
+        layout: TreeLayout,
+    ) -> Vec<(Id<E>, T)> {
+        let ids = alloc.create_range(tree.node_count());
+        let start = ids.range_usize().start;
+        let ids = ids.into_iter().collect::<Vec<_>>();
+
+        let mut parents = vec![None; ids.len()];
+        let mut children = vec![IdRange::default(); ids.len()];
+        let mut values = Vec::with_capacity(ids.len());
+        values.resize_with(ids.len(), || None);
+
+        let mut next = 1;
+        let mut queue = VecDeque::from([(0, tree)]);
+        while let Some((node, tree)) = match layout {
+            TreeLayout::DepthFirst => queue.pop_back(),
+            TreeLayout::BreadthFirst => queue.pop_front(),
+        } {
+            let block = next..next + tree.children.len();
+            next = block.end;
+
+            children[node] = IdRange::new((start + block.start) as u32, (start + block.end) as u32);
+            for child in block.clone() {
+                parents[child] = Some(ids[node]);
+            }
+            values[node] = Some(tree.value);
+
+            let nodes = block.zip(tree.children);
+            match layout {
+                TreeLayout::DepthFirst => queue.extend(nodes.rev()),
+                TreeLayout::BreadthFirst => queue.extend(nodes),
+            }
+        }
+
+        for (i, id) in ids.iter().enumerate() {
+            let children = Some(children[i]).filter(|c| !c.is_empty());
+            let relation = RangeRelation::new(parents[i], children);
+            self.insert_filled(*id, relation);
+        }
+
+        ids.into_iter().zip(values.into_iter().flatten()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::Stat;
+
+    #[test]
+    fn depth_first() {
+        let mut alloc = RangeAllocator::<Stat>::default();
+        let mut relations = RangeRelations::<Stat>::default();
+        let id = |i: u32| Id::<Stat>::new(i, ());
+
+        let tree = RangeTree::new('a').with_children([
+            RangeTree::new('b').with_children([
+                RangeTree::new('d').with_child(RangeTree::new('g')),
+                RangeTree::new('e'),
+            ]),
+            RangeTree::new('c').with_child(RangeTree::new('f')),
+        ]);
+        alloc.create();
+        let created = relations.create_tree(&mut alloc, tree, TreeLayout::DepthFirst);
+        let values = created.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+
+        assert_eq!(vec!['a', 'b', 'c', 'd', 'e', 'g', 'f'], values);
+        assert!(relations[id(0)].is_unlinked());
+        assert_eq!(
+            RangeRelation::ParentOf(IdRange::new(2, 4)),
+            relations[id(1)]
+        );
+        assert_eq!(
+            RangeRelation::ChildAndParentOf(id(1), IdRange::new(4, 6)),
+            relations[id(2)]
+        );
+        assert_eq!(Some(IdRange::new(4, 7)), relations.descendant_range(id(2)));
+        assert_eq!(Some(IdRange::new(2, 8)), relations.descendant_range(id(1)));
+    }
+
+    #[test]
+    fn breadth_first() {
+        let mut alloc = RangeAllocator::<Stat>::default();
+        let mut relations = RangeRelations::<Stat>::default();
+        let id = |i: u32| Id::<Stat>::new(i, ());
+
+        let tree = RangeTree::new('a').with_children([
+            RangeTree::new('b').with_children([
+                RangeTree::new('d').with_child(RangeTree::new('g')),
+                RangeTree::new('e'),
+            ]),
+            RangeTree::new('c').with_child(RangeTree::new('f')),
+        ]);
+        alloc.create();
+        let created = relations.create_tree(&mut alloc, tree, TreeLayout::BreadthFirst);
+        let values = created.iter().map(|(_, v)| *v).collect::<Vec<_>>();
+
+        assert_eq!(vec!['a', 'b', 'c', 'd', 'e', 'f', 'g'], values);
+        assert_eq!(
+            RangeRelation::ChildAndParentOf(id(2), IdRange::new(7, 8)),
+            relations[id(4)]
+        );
+        assert_eq!(
+            RangeRelation::ChildAndParentOf(id(1), IdRange::new(6, 7)),
+            relations[id(3)]
+        );
+        assert_eq!(RangeRelation::ChildOf(id(3)), relations[id(6)]);
+        assert_eq!(id(1), relations.root_of(id(6)));
+    }
+
+    #[test]
+    fn depth_first_keeps_subtrees_contiguous() {
+        let mut alloc = RangeAllocator::<Stat>::default();
+        let mut relations = RangeRelations::<Stat>::default();
+
+        let tree = RangeTree::new(0).with_children([
+            RangeTree::new(1).with_child(RangeTree::new(3).with_child(RangeTree::new(5))),
+            RangeTree::new(2).with_child(RangeTree::new(4)),
+        ]);
+        let created = relations.create_tree(&mut alloc, tree, TreeLayout::DepthFirst);
+
+        assert_eq!(6, alloc.ids().len());
+        for (id, _) in created {
+            assert!(relations.descendant_range(id).is_some());
+        }
+    }
+}
@@ -0,0 +1,653 @@
+//! Links between the Ids of two dynamic entity types, with lookups in both directions.
+//!
+//! Each relation tracks the killed Ids of both entity types,
+//! so it must be told about the kills of either allocator and validated against both.
+
+use crate::allocator::KilledIds;
+use crate::gen::AllocGen;
+use crate::valid::{kill_each, Validator};
+use crate::{DynamicIdType, Entity, Id, IdMultiMap, SyncError, Valid, ValidId};
+use fxhash::FxHashMap;
+
+/// Panics if the relation is out of sync with the allocator of either entity
+#[inline]
+#[track_caller]
+fn assert_in_sync<A: Entity, B: Entity>(a: Result<(), SyncError<A>>, b: Result<(), SyncError<B>>) {
+    if let Err(error) = a {
+        panic!(
+            "relation is out of sync with the allocator: {}: {error}",
+            std::any::type_name::<A>()
+        );
+    }
+    if let Err(error) = b {
+        panic!(
+            "relation is out of sync with the allocator: {}: {error}",
+            std::any::type_name::<B>()
+        );
+    }
+}
+
+macro_rules! relation_gens {
+    ($relation:ident) => {
+        impl<A: Entity<IdType: DynamicIdType>, B: Entity<IdType: DynamicIdType>> $relation<A, B> {
+            #[inline]
+            #[track_caller]
+            pub fn kill_many_a(&mut self, killed: &KilledIds<A>) {
+                kill_each(
+                    self,
+                    |relation| &relation.gen_a,
+                    killed,
+                    |relation, id| relation.kill_a(Valid::new(id)),
+                );
+            }
+
+            #[inline]
+            #[track_caller]
+            pub fn kill_many_b(&mut self, killed: &KilledIds<B>) {
+                kill_each(
+                    self,
+                    |relation| &relation.gen_b,
+                    killed,
+                    |relation, id| relation.kill_b(Valid::new(id)),
+                );
+            }
+
+            #[inline]
+            #[track_caller]
+            pub fn validate<'v, VA: Validator<'v, A>, VB: Validator<'v, B>>(
+                &self,
+                a: VA,
+                b: VB,
+            ) -> &Valid<'v, Self> {
+                assert_in_sync(self.try_validate_a(a), self.try_validate_b(b));
+                Valid::new_ref(self)
+            }
+
+            #[inline]
+            #[track_caller]
+            pub fn validate_mut<'v, VA: Validator<'v, A>, VB: Validator<'v, B>>(
+                &mut self,
+                a: VA,
+                b: VB,
+            ) -> &mut Valid<'v, Self> {
+                assert_in_sync(self.try_validate_a(a), self.try_validate_b(b));
+                Valid::new_mut(self)
+            }
+
+            /// Returns how the relation is out of sync with the allocator of `A`
+            #[inline]
+            pub fn try_validate_a<'v, V: Validator<'v, A>>(
+                &self,
+                v: V,
+            ) -> Result<(), SyncError<A>> {
+                v.check(&self.gen_a)
+            }
+
+            /// Returns how the relation is out of sync with the allocator of `B`
+            #[inline]
+            pub fn try_validate_b<'v, V: Validator<'v, B>>(
+                &self,
+                v: V,
+            ) -> Result<(), SyncError<B>> {
+                v.check(&self.gen_b)
+            }
+        }
+    };
+}
+
+/// Links each `Id<A>` to at most one `Id<B>`, and each `Id<B>` to at most one `Id<A>`
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OneToOne<A: Entity, B: Entity> {
+    forward: FxHashMap<Id<A>, Id<B>>,
+    reverse: FxHashMap<Id<B>, Id<A>>,
+    gen_a: AllocGen<A>,
+    gen_b: AllocGen<B>,
+}
+
+impl<A: Entity, B: Entity> Default for OneToOne<A, B> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            forward: Default::default(),
+            reverse: Default::default(),
+            gen_a: Default::default(),
+            gen_b: Default::default(),
+        }
+    }
+}
+
+impl<A: Entity, B: Entity> Clone for OneToOne<A, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            forward: self.forward.clone(),
+            reverse: self.reverse.clone(),
+            gen_a: self.gen_a.clone(),
+            gen_b: self.gen_b.clone(),
+        }
+    }
+}
+
+impl<A: Entity, B: Entity> OneToOne<A, B> {
+    /// Links the Ids, replacing any existing links of either
+    pub fn link<VA: ValidId<Entity = A>, VB: ValidId<Entity = B>>(&mut self, a: VA, b: VB) {
+        let (a, b) = (a.id(), b.id());
+        self.remove_a(a);
+        self.remove_b(b);
+        self.forward.insert(a, b);
+        self.reverse.insert(b, a);
+    }
+
+    /// Returns true if the Ids were linked
+    pub fn unlink<VA: ValidId<Entity = A>, VB: ValidId<Entity = B>>(
+        &mut self,
+        a: VA,
+        b: VB,
+    ) -> bool {
+        let (a, b) = (a.id(), b.id());
+        let linked = self.forward.get(&a) == Some(&b);
+        if linked {
+            self.forward.remove(&a);
+            self.reverse.remove(&b);
+        }
+        linked
+    }
+
+    fn remove_a(&mut self, a: Id<A>) -> Option<Id<B>> {
+        let b = self.forward.remove(&a)?;
+        self.reverse.remove(&b);
+        Some(b)
+    }
+
+    fn remove_b(&mut self, b: Id<B>) -> Option<Id<A>> {
+        let a = self.reverse.remove(&b)?;
+        self.forward.remove(&a);
+        Some(a)
+    }
+
+    #[inline]
+    pub fn forward<V: ValidId<Entity = A>>(&self, a: V) -> Option<Id<B>> {
+        self.forward.get(&a.id()).copied()
+    }
+
+    #[inline]
+    pub fn reverse<V: ValidId<Entity = B>>(&self, b: V) -> Option<Id<A>> {
+        self.reverse.get(&b.id()).copied()
+    }
+
+    /// The number of links
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.forward.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.forward.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Id<A>, Id<B>)> + '_ {
+        self.forward.iter().map(|(a, b)| (*a, *b))
+    }
+}
+
+impl<A: Entity<IdType: DynamicIdType>, B: Entity<IdType: DynamicIdType>> OneToOne<A, B> {
+    #[inline]
+    pub fn kill_a<V: ValidId<Entity = A>>(&mut self, a: V) -> Option<Id<B>> {
+        let a = a.id();
+        self.gen_a.increment(a);
+        self.remove_a(a)
+    }
+
+    #[inline]
+    pub fn kill_b<V: ValidId<Entity = B>>(&mut self, b: V) -> Option<Id<A>> {
+        let b = b.id();
+        self.gen_b.increment(b);
+        self.remove_b(b)
+    }
+}
+
+relation_gens!(OneToOne);
+
+impl<'v, A: Entity, B: Entity> Valid<'v, OneToOne<A, B>> {
+    #[inline]
+    pub fn forward<V: ValidId<Entity = A>>(&self, a: V) -> Option<Valid<'v, Id<B>>> {
+        self.value.forward(a).map(Valid::new)
+    }
+
+    #[inline]
+    pub fn reverse<V: ValidId<Entity = B>>(&self, b: V) -> Option<Valid<'v, Id<A>>> {
+        self.value.reverse(b).map(Valid::new)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Valid<'v, Id<A>>, Valid<'v, Id<B>>)> + '_ {
+        self.value
+            .iter()
+            .map(|(a, b)| (Valid::new(a), Valid::new(b)))
+    }
+}
+
+/// Links each `Id<A>` to many `Id<B>`, and each `Id<B>` to at most one `Id<A>`
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OneToMany<A: Entity, B: Entity> {
+    forward: FxHashMap<Id<A>, Vec<Id<B>>>,
+    reverse: FxHashMap<Id<B>, Id<A>>,
+    gen_a: AllocGen<A>,
+    gen_b: AllocGen<B>,
+}
+
+impl<A: Entity, B: Entity> Default for OneToMany<A, B> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            forward: Default::default(),
+            reverse: Default::default(),
+            gen_a: Default::default(),
+            gen_b: Default::default(),
+        }
+    }
+}
+
+impl<A: Entity, B: Entity> Clone for OneToMany<A, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            forward: self.forward.clone(),
+            reverse: self.reverse.clone(),
+            gen_a: self.gen_a.clone(),
+            gen_b: self.gen_b.clone(),
+        }
+    }
+}
+
+impl<A: Entity, B: Entity> OneToMany<A, B> {
+    /// Links the Ids, replacing the existing link of `b`
+    pub fn link<VA: ValidId<Entity = A>, VB: ValidId<Entity = B>>(&mut self, a: VA, b: VB) {
+        let (a, b) = (a.id(), b.id());
+        if self.reverse.get(&b) == Some(&a) {
+            return;
+        }
+
+        self.remove_b(b);
+        self.forward.entry(a).or_default().push(b);
+        self.reverse.insert(b, a);
+    }
+
+    /// Returns true if the Ids were linked
+    pub fn unlink<VA: ValidId<Entity = A>, VB: ValidId<Entity = B>>(
+        &mut self,
+        a: VA,
+        b: VB,
+    ) -> bool {
+        let (a, b) = (a.id(), b.id());
+        let linked = self.reverse.get(&b) == Some(&a);
+        if linked {
+            self.remove_b(b);
+        }
+        linked
+    }
+
+    fn remove_a(&mut self, a: Id<A>) -> Vec<Id<B>> {
+        let bs = self.forward.remove(&a).unwrap_or_default();
+        for b in &bs {
+            self.reverse.remove(b);
+        }
+        bs
+    }
+
+    fn remove_b(&mut self, b: Id<B>) -> Option<Id<A>> {
+        let a = self.reverse.remove(&b)?;
+        if let Some(bs) = self.forward.get_mut(&a) {
+            bs.retain(|id| *id != b);
+            if bs.is_empty() {
+                self.forward.remove(&a);
+            }
+        }
+        Some(a)
+    }
+
+    /// The Ids linked to `a`, in the order they were linked
+    #[inline]
+    pub fn forward<V: ValidId<Entity = A>>(&self, a: V) -> &[Id<B>] {
+        self.forward.get(&a.id()).map_or(&[], Vec::as_slice)
+    }
+
+    #[inline]
+    pub fn reverse<V: ValidId<Entity = B>>(&self, b: V) -> Option<Id<A>> {
+        self.reverse.get(&b.id()).copied()
+    }
+
+    /// The number of links
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.reverse.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.reverse.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Id<A>, &[Id<B>])> + '_ {
+        self.forward.iter().map(|(a, bs)| (*a, bs.as_slice()))
+    }
+}
+
+impl<A: Entity<IdType: DynamicIdType>, B: Entity<IdType: DynamicIdType>> OneToMany<A, B> {
+    /// Removes all of the links of `a`, and returns the Ids it was linked to
+    #[inline]
+    pub fn kill_a<V: ValidId<Entity = A>>(&mut self, a: V) -> Vec<Id<B>> {
+        let a = a.id();
+        self.gen_a.increment(a);
+        self.remove_a(a)
+    }
+
+    #[inline]
+    pub fn kill_b<V: ValidId<Entity = B>>(&mut self, b: V) -> Option<Id<A>> {
+        let b = b.id();
+        self.gen_b.increment(b);
+        self.remove_b(b)
+    }
+}
+
+relation_gens!(OneToMany);
+
+impl<'v, A: Entity, B: Entity> Valid<'v, OneToMany<A, B>> {
+    #[inline]
+    pub fn forward<V: ValidId<Entity = A>>(
+        &self,
+        a: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<B>>> + '_ {
+        self.value.forward(a).iter().copied().map(Valid::new)
+    }
+
+    #[inline]
+    pub fn reverse<V: ValidId<Entity = B>>(&self, b: V) -> Option<Valid<'v, Id<A>>> {
+        self.value.reverse(b).map(Valid::new)
+    }
+}
+
+/// Links many `Id<A>` to many `Id<B>`
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ManyToMany<A: Entity, B: Entity> {
+    map: IdMultiMap<A, B>,
+}
+
+impl<A: Entity, B: Entity> Default for ManyToMany<A, B> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            map: Default::default(),
+        }
+    }
+}
+
+impl<A: Entity, B: Entity> Clone for ManyToMany<A, B> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            map: self.map.clone(),
+        }
+    }
+}
+
+impl<A: Entity, B: Entity> ManyToMany<A, B> {
+    /// Returns true if the Ids were not already linked
+    #[inline]
+    pub fn link<VA: ValidId<Entity = A>, VB: ValidId<Entity = B>>(&mut self, a: VA, b: VB) -> bool {
+        self.map.insert(a, b)
+    }
+
+    /// Returns true if the Ids were linked
+    #[inline]
+    pub fn unlink<VA: ValidId<Entity = A>, VB: ValidId<Entity = B>>(
+        &mut self,
+        a: VA,
+        b: VB,
+    ) -> bool {
+        self.map.remove_value(a, b)
+    }
+
+    /// The Ids linked to `a`, in the order they were linked
+    #[inline]
+    pub fn forward<V: ValidId<Entity = A>>(&self, a: V) -> &[Id<B>] {
+        self.map.get_all(a)
+    }
+
+    /// The Ids linked to `b`, in the order they were linked
+    #[inline]
+    pub fn reverse<V: ValidId<Entity = B>>(&self, b: V) -> &[Id<A>] {
+        self.map.keys_of(b)
+    }
+
+    #[inline]
+    pub fn is_linked<VA: ValidId<Entity = A>, VB: ValidId<Entity = B>>(
+        &self,
+        a: VA,
+        b: VB,
+    ) -> bool {
+        self.map.contains(a, b)
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&Id<A>, &[Id<B>])> + '_ {
+        self.map.iter()
+    }
+}
+
+impl<A: Entity<IdType: DynamicIdType>, B: Entity<IdType: DynamicIdType>> ManyToMany<A, B> {
+    /// Removes all of the links of `a`, and returns the Ids it was linked to
+    #[inline]
+    pub fn kill_a<V: ValidId<Entity = A>>(&mut self, a: V) -> Vec<Id<B>> {
+        self.map.kill_key(a)
+    }
+
+    #[inline]
+    pub fn kill_b<V: ValidId<Entity = B>>(&mut self, b: V) {
+        self.map.kill_value(b);
+    }
+
+    #[inline]
+    pub fn kill_many_a(&mut self, killed: &KilledIds<A>) {
+        self.map.kill_many_keys(killed);
+    }
+
+    #[inline]
+    pub fn kill_many_b(&mut self, killed: &KilledIds<B>) {
+        self.map.kill_many_values(killed);
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate<'v, VA: Validator<'v, A>, VB: Validator<'v, B>>(
+        &self,
+        a: VA,
+        b: VB,
+    ) -> &Valid<'v, Self> {
+        assert_in_sync(self.try_validate_a(a), self.try_validate_b(b));
+        Valid::new_ref(self)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_mut<'v, VA: Validator<'v, A>, VB: Validator<'v, B>>(
+        &mut self,
+        a: VA,
+        b: VB,
+    ) -> &mut Valid<'v, Self> {
+        assert_in_sync(self.try_validate_a(a), self.try_validate_b(b));
+        Valid::new_mut(self)
+    }
+
+    /// Returns how the relation is out of sync with the allocator of `A`
+    #[inline]
+    pub fn try_validate_a<'v, V: Validator<'v, A>>(&self, v: V) -> Result<(), SyncError<A>> {
+        self.map.try_validate_keys(v)
+    }
+
+    /// Returns how the relation is out of sync with the allocator of `B`
+    #[inline]
+    pub fn try_validate_b<'v, V: Validator<'v, B>>(&self, v: V) -> Result<(), SyncError<B>> {
+        self.map.try_validate_values(v)
+    }
+}
+
+impl<'v, A: Entity, B: Entity> Valid<'v, ManyToMany<A, B>> {
+    #[inline]
+    pub fn forward<V: ValidId<Entity = A>>(
+        &self,
+        a: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<B>>> + '_ {
+        self.value.forward(a).iter().copied().map(Valid::new)
+    }
+
+    #[inline]
+    pub fn reverse<V: ValidId<Entity = B>>(
+        &self,
+        b: V,
+    ) -> impl Iterator<Item = Valid<'v, Id<A>>> + '_ {
+        self.value.reverse(b).iter().copied().map(Valid::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Dyn, Dyn8};
+    use crate::Allocator;
+
+    #[test]
+    fn one_to_one_link_replaces_existing() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let mut relation = OneToOne::<Dyn, Dyn8>::default();
+
+        let ids_a = a.create_many(2).value;
+        let id_b = b.create().value;
+
+        relation.link(Valid::new(ids_a[0]), Valid::new(id_b));
+        relation.link(Valid::new(ids_a[1]), Valid::new(id_b));
+
+        assert_eq!(None, relation.forward(Valid::new(ids_a[0])));
+        assert_eq!(Some(id_b), relation.forward(Valid::new(ids_a[1])));
+        assert_eq!(Some(ids_a[1]), relation.reverse(Valid::new(id_b)));
+        assert_eq!(1, relation.len());
+    }
+
+    #[test]
+    fn one_to_one_kill_many_b() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let mut relation = OneToOne::<Dyn, Dyn8>::default();
+
+        let id_a = a.create();
+        let id_b = b.create().value;
+        relation.link(id_a, Valid::new(id_b));
+
+        let killed = b.kill_many(&mut vec![id_b]);
+        relation.kill_many_b(&killed);
+
+        let id_a = id_a.value;
+        let relation = relation.validate(&a, &b);
+        assert_eq!(None, relation.forward(Valid::new(id_a)));
+    }
+
+    #[test]
+    fn one_to_many_link_moves_b() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let mut relation = OneToMany::<Dyn, Dyn8>::default();
+
+        let ids_a = a.create_many(2).value;
+        let ids_b = b.create_many(2).value;
+
+        relation.link(Valid::new(ids_a[0]), Valid::new(ids_b[0]));
+        relation.link(Valid::new(ids_a[0]), Valid::new(ids_b[1]));
+        relation.link(Valid::new(ids_a[1]), Valid::new(ids_b[0]));
+
+        assert_eq!(&ids_b[1..2], relation.forward(Valid::new(ids_a[0])));
+        assert_eq!(&ids_b[0..1], relation.forward(Valid::new(ids_a[1])));
+        assert_eq!(Some(ids_a[1]), relation.reverse(Valid::new(ids_b[0])));
+        assert_eq!(2, relation.len());
+    }
+
+    #[test]
+    fn one_to_many_kill_many_a() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let mut relation = OneToMany::<Dyn, Dyn8>::default();
+
+        let id_a = a.create().value;
+        let ids_b = b.create_many(3).value;
+        for id in &ids_b {
+            relation.link(Valid::new(id_a), Valid::new(*id));
+        }
+
+        let killed = a.kill_many(&mut vec![id_a]);
+        relation.kill_many_a(&killed);
+
+        let relation = relation.validate(&a, &b);
+        assert!(ids_b
+            .iter()
+            .all(|id| relation.reverse(Valid::new(*id)).is_none()));
+    }
+
+    #[test]
+    fn many_to_many_kill_many_b() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let mut relation = ManyToMany::<Dyn, Dyn8>::default();
+
+        let ids_a = a.create_many(2).value;
+        let ids_b = b.create_many(3).value;
+        for (id_a, id_b) in ids_a.iter().zip(&ids_b) {
+            relation.link(Valid::new(*id_a), Valid::new(*id_b));
+            relation.link(Valid::new(*id_a), Valid::new(ids_b[2]));
+        }
+        assert_eq!(&ids_a, relation.reverse(Valid::new(ids_b[2])));
+
+        let killed = b.kill_many(&mut vec![ids_b[2]]);
+        relation.kill_many_b(&killed);
+
+        let relation = relation.validate(&a, &b);
+        let forward = relation.forward(Valid::new(ids_a[0])).collect::<Vec<_>>();
+        assert_eq!(vec![ids_b[0]], forward);
+    }
+
+    #[test]
+    #[should_panic(expected = "relation is out of sync")]
+    fn validate_when_out_of_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let b = Allocator::<Dyn8>::default();
+        let relation = OneToMany::<Dyn, Dyn8>::default();
+
+        let id = a.create().value;
+        a.kill(id);
+
+        relation.validate(&a, &b);
+    }
+
+    #[test]
+    #[should_panic(expected = "relation is out of sync")]
+    fn many_to_many_validate_when_out_of_sync() {
+        let a = Allocator::<Dyn>::default();
+        let mut b = Allocator::<Dyn8>::default();
+        let relation = ManyToMany::<Dyn, Dyn8>::default();
+
+        let id = b.create().value;
+        b.kill(id);
+
+        relation.validate(&a, &b);
+    }
+}
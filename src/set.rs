@@ -0,0 +1,318 @@
+use crate::allocator::{IdRemap, KillListener, KilledIds};
+use crate::gen::AllocGen;
+use crate::valid::{Synced, Validator};
+use crate::{DynamicIdType, Entity, Id, SyncError, Valid, ValidId};
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IdSet<E: Entity> {
+    set: fxhash::FxHashSet<Id<E>>,
+    gen: AllocGen<E>,
+}
+
+impl<E: Entity> Default for IdSet<E> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            set: Default::default(),
+            gen: Default::default(),
+        }
+    }
+}
+
+impl<E: Entity> Clone for IdSet<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            set: self.set.clone(),
+            gen: self.gen.clone(),
+        }
+    }
+}
+
+impl<E: Entity> PartialEq for IdSet<E> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.set.eq(&other.set) && self.gen.eq(&other.gen)
+    }
+}
+
+impl<E: Entity> Eq for IdSet<E> {}
+
+impl<E: Entity> IdSet<E> {
+    /// Returns true if the Id was not already in the set
+    #[inline]
+    pub fn insert<V: ValidId<Entity = E>>(&mut self, id: V) -> bool {
+        self.set.insert(id.id())
+    }
+
+    /// Returns true if the Id was in the set
+    #[inline]
+    pub fn remove<V: ValidId<Entity = E>>(&mut self, id: V) -> bool {
+        self.set.remove(&id.id())
+    }
+
+    #[inline]
+    pub fn contains<V: ValidId<Entity = E>>(&self, id: V) -> bool {
+        self.set.contains(&id.id())
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.set.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.set.clear();
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &Id<E>> + '_ {
+        self.set.iter()
+    }
+
+    /// Ids that are in either set
+    #[inline]
+    pub fn union<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a Id<E>> + 'a {
+        self.set.union(&other.set)
+    }
+
+    /// Ids that are in both sets
+    #[inline]
+    pub fn intersection<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a Id<E>> + 'a {
+        self.set.intersection(&other.set)
+    }
+
+    /// Ids that are in this set but not the other
+    #[inline]
+    pub fn difference<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = &'a Id<E>> + 'a {
+        self.set.difference(&other.set)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> IdSet<E> {
+    #[inline]
+    pub fn kill<V: ValidId<Entity = E>>(&mut self, id: V) -> bool {
+        let id = id.id();
+        self.gen.increment(id);
+        self.set.remove(&id)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn kill_many(&mut self, killed: &KilledIds<E>) {
+        Synced::kill_many(self, killed);
+    }
+
+    /// Moves Ids to their new values from a compacted allocator
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        assert_eq!(&self.gen, remap.before());
+        for (old, new) in remap.moved() {
+            if self.set.remove(old) {
+                self.set.insert(*new);
+            }
+        }
+        self.gen.clone_from(remap.after());
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate<'v, V: Validator<'v, E>>(&self, v: V) -> &Valid<'v, Self> {
+        Synced::validate(self, v)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_mut<'v, V: Validator<'v, E>>(&mut self, v: V) -> &mut Valid<'v, Self> {
+        Synced::validate_mut(self, v)
+    }
+
+    /// Returns how the collection is out of sync with the allocator instead of panicking
+    #[inline]
+    pub fn try_validate<'v, V: Validator<'v, E>>(
+        &self,
+        v: V,
+    ) -> Result<&Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate(self, v)
+    }
+
+    #[inline]
+    pub fn try_validate_mut<'v, V: Validator<'v, E>>(
+        &mut self,
+        v: V,
+    ) -> Result<&mut Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate_mut(self, v)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> Synced<E> for IdSet<E> {
+    #[inline]
+    fn alloc_gen(&self) -> &AllocGen<E> {
+        &self.gen
+    }
+
+    #[inline]
+    fn kill_id(&mut self, id: Id<E>) {
+        self.kill(Valid::new(id));
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>> KillListener<E> for IdSet<E> {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        IdSet::kill_many(self, killed);
+    }
+}
+
+impl<'v, E: Entity> Valid<'v, IdSet<E>> {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = Valid<'v, Id<E>>> + '_ {
+        self.value.iter().copied().map(Valid::new)
+    }
+
+    #[inline]
+    pub fn union<'a>(&'a self, other: &'a Self) -> impl Iterator<Item = Valid<'v, Id<E>>> + 'a {
+        self.value.union(&other.value).copied().map(Valid::new)
+    }
+
+    #[inline]
+    pub fn intersection<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = Valid<'v, Id<E>>> + 'a {
+        self.value
+            .intersection(&other.value)
+            .copied()
+            .map(Valid::new)
+    }
+
+    #[inline]
+    pub fn difference<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = Valid<'v, Id<E>>> + 'a {
+        self.value.difference(&other.value).copied().map(Valid::new)
+    }
+}
+
+impl<E: Entity, V: ValidId<Entity = E>> FromIterator<V> for IdSet<E> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = V>>(iter: I) -> Self {
+        let mut set = Self::default();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<E: Entity, V: ValidId<Entity = E>> Extend<V> for IdSet<E> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = V>>(&mut self, iter: I) {
+        self.set.extend(iter.into_iter().map(|id| id.id()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{Dyn, Stat};
+    use crate::Allocator;
+
+    #[test]
+    fn insert_remove_contains() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut set = IdSet::<Dyn>::default();
+
+        let id = a.create();
+
+        assert!(set.insert(id));
+        assert!(!set.insert(id));
+        assert!(set.contains(id));
+        assert!(set.remove(id));
+        assert!(!set.contains(id));
+    }
+
+    #[test]
+    fn set_operations() {
+        let a = (0..3).map(|i| Id::<Stat>::new(i, ())).collect::<IdSet<_>>();
+        let b = (2..4).map(|i| Id::<Stat>::new(i, ())).collect::<IdSet<_>>();
+
+        let sorted = |iter: &mut dyn Iterator<Item = &Id<Stat>>| {
+            let mut ids = iter.map(|id| id.index()).collect::<Vec<_>>();
+            ids.sort_unstable();
+            ids
+        };
+
+        assert_eq!(vec![0, 1, 2, 3], sorted(&mut a.union(&b)));
+        assert_eq!(vec![2], sorted(&mut a.intersection(&b)));
+        assert_eq!(vec![0, 1], sorted(&mut a.difference(&b)));
+    }
+
+    #[test]
+    fn kill_many() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut set = IdSet::<Dyn>::default();
+
+        let ids = a.create_many(2).value;
+        set.extend(ids.iter().map(Valid::new));
+
+        let killed = a.kill_many(&mut vec![ids[0]]);
+        set.kill_many(&killed);
+
+        let remaining = set.validate(&a).iter().collect::<Vec<_>>();
+        assert_eq!(vec![ids[1]], remaining);
+    }
+
+    #[test]
+    fn valid_set_operations() {
+        let mut a = Allocator::<Dyn>::default();
+        let ids = a.create_many(3).value;
+
+        let mut x = IdSet::<Dyn>::default();
+        let mut y = IdSet::<Dyn>::default();
+        x.extend(ids[..2].iter().map(Valid::new));
+        y.extend(ids[1..].iter().map(Valid::new));
+
+        let x = x.validate(&a);
+        let y = y.validate(&a);
+
+        assert_eq!(vec![ids[1]], x.intersection(y).collect::<Vec<_>>());
+        assert_eq!(vec![ids[0]], x.difference(y).collect::<Vec<_>>());
+        assert_eq!(3, x.union(y).count());
+    }
+
+    #[test]
+    #[should_panic]
+    fn validate_when_out_of_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut set = IdSet::<Dyn>::default();
+
+        let id = a.create();
+        set.insert(id);
+
+        let id = id.value;
+        a.kill(id);
+
+        set.validate(&a);
+    }
+
+    #[test]
+    fn validate_mut_when_in_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut set = IdSet::<Dyn>::default();
+
+        let id = a.create();
+        set.insert(id);
+
+        set.kill(id);
+        let id = id.value;
+        a.kill(id);
+
+        set.validate_mut(&a);
+    }
+}
@@ -0,0 +1,409 @@
+use crate::allocator::{IdRemap, KillListener, KilledIds};
+use crate::gen::AllocGen;
+use crate::id::NonMaxU32;
+use crate::valid::{Synced, Validator};
+use crate::{DynamicIdType, Entity, Id, SyncError, Valid, ValidId};
+
+/// A sparse set of component values, for components that only a small share of entities have.
+///
+/// Values are packed into a dense array,
+/// and each `Id<E>` index maps into it through a sparse array of slots.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SparseComponent<E: Entity, T> {
+    sparse: Vec<Option<NonMaxU32>>,
+    dense: Vec<T>,
+    ids: Vec<Id<E>>,
+    gen: AllocGen<E>,
+}
+
+impl<E: Entity, T> Default for SparseComponent<E, T> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            sparse: Default::default(),
+            dense: Default::default(),
+            ids: Default::default(),
+            gen: Default::default(),
+        }
+    }
+}
+
+impl<E: Entity, T: Clone> Clone for SparseComponent<E, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            sparse: self.sparse.clone(),
+            dense: self.dense.clone(),
+            ids: self.ids.clone(),
+            gen: self.gen.clone(),
+        }
+    }
+}
+
+impl<E: Entity, T> SparseComponent<E, T> {
+    #[inline]
+    fn dense_index(&self, id: Id<E>) -> Option<usize> {
+        let index = self.sparse.get(id.index()).copied().flatten()?.get() as usize;
+        self.ids[index].eq(&id).then_some(index)
+    }
+
+    #[inline]
+    pub fn insert<V: ValidId<Entity = E>>(&mut self, id: V, value: T) -> Option<T> {
+        let id = id.id();
+
+        if let Some(index) = self.dense_index(id) {
+            return Some(std::mem::replace(&mut self.dense[index], value));
+        }
+
+        // a value left behind by an older generation of the index is replaced in place
+        if let Some(index) = self.sparse.get(id.index()).copied().flatten() {
+            let index = index.get() as usize;
+            if self.ids[index].index() == id.index() {
+                self.dense[index] = value;
+                self.ids[index] = id;
+                return None;
+            }
+        }
+
+        if self.sparse.len() <= id.index() {
+            self.sparse.resize(id.index() + 1, None);
+        }
+
+        self.sparse[id.index()] = NonMaxU32::new(self.dense.len() as u32);
+        self.dense.push(value);
+        self.ids.push(id);
+        None
+    }
+
+    #[inline]
+    pub fn remove<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<T> {
+        self.remove_id(id.id())
+    }
+
+    fn remove_id(&mut self, id: Id<E>) -> Option<T> {
+        let index = self.dense_index(id)?;
+        self.sparse[id.index()] = None;
+
+        let value = self.dense.swap_remove(index);
+        self.ids.swap_remove(index);
+
+        // the last value was moved into the removed slot
+        if let Some(moved) = self.ids.get(index) {
+            self.sparse[moved.index()] = NonMaxU32::new(index as u32);
+        }
+
+        Some(value)
+    }
+
+    #[inline]
+    pub fn contains<V: ValidId<Entity = E>>(&self, id: V) -> bool {
+        self.dense_index(id.id()).is_some()
+    }
+
+    #[inline]
+    pub fn get<V: ValidId<Entity = E>>(&self, id: V) -> Option<&T> {
+        let index = self.dense_index(id.id())?;
+        self.dense.get(index)
+    }
+
+    #[inline]
+    pub fn get_mut<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<&mut T> {
+        let index = self.dense_index(id.id())?;
+        self.dense.get_mut(index)
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    /// The packed values, in the same order as [`SparseComponent::ids`]
+    #[inline]
+    pub fn values(&self) -> &[T] {
+        &self.dense
+    }
+
+    #[inline]
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.dense
+    }
+
+    /// The packed Ids, in the same order as [`SparseComponent::values`]
+    #[inline]
+    pub fn ids(&self) -> &[Id<E>] {
+        &self.ids
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&Id<E>, &T)> + '_ {
+        self.ids.iter().zip(&self.dense)
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Id<E>, &mut T)> + '_ {
+        self.ids.iter().zip(&mut self.dense)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> SparseComponent<E, T> {
+    #[inline]
+    pub fn kill<V: ValidId<Entity = E>>(&mut self, id: V) -> Option<T> {
+        let id = id.id();
+        self.gen.increment(id);
+        self.remove_id(id)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn kill_many(&mut self, killed: &KilledIds<E>) {
+        Synced::kill_many(self, killed);
+    }
+
+    /// Moves values to the new Ids from a compacted allocator
+    pub fn remap(&mut self, remap: &IdRemap<E>) {
+        assert_eq!(&self.gen, remap.before());
+        for (old, new) in remap.moved() {
+            if let Some(index) = self.dense_index(*old) {
+                self.sparse[old.index()] = None;
+                self.sparse[new.index()] = NonMaxU32::new(index as u32);
+                self.ids[index] = *new;
+            }
+        }
+        self.sparse.truncate(remap.slots());
+        self.gen.clone_from(remap.after());
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate<'v, V: Validator<'v, E>>(&self, v: V) -> &Valid<'v, Self> {
+        Synced::validate(self, v)
+    }
+
+    #[inline]
+    #[track_caller]
+    pub fn validate_mut<'v, V: Validator<'v, E>>(&mut self, v: V) -> &mut Valid<'v, Self> {
+        Synced::validate_mut(self, v)
+    }
+
+    /// Returns how the collection is out of sync with the allocator instead of panicking
+    #[inline]
+    pub fn try_validate<'v, V: Validator<'v, E>>(
+        &self,
+        v: V,
+    ) -> Result<&Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate(self, v)
+    }
+
+    #[inline]
+    pub fn try_validate_mut<'v, V: Validator<'v, E>>(
+        &mut self,
+        v: V,
+    ) -> Result<&mut Valid<'v, Self>, SyncError<E>> {
+        Synced::try_validate_mut(self, v)
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> Synced<E> for SparseComponent<E, T> {
+    #[inline]
+    fn alloc_gen(&self) -> &AllocGen<E> {
+        &self.gen
+    }
+
+    #[inline]
+    fn kill_id(&mut self, id: Id<E>) {
+        self.kill(Valid::new(id));
+    }
+}
+
+impl<E: Entity<IdType: DynamicIdType>, T> KillListener<E> for SparseComponent<E, T> {
+    #[inline]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        SparseComponent::kill_many(self, killed);
+    }
+}
+
+impl<E: Entity, T, V: ValidId<Entity = E>> std::ops::Index<V> for SparseComponent<E, T> {
+    type Output = T;
+    #[inline]
+    #[track_caller]
+    fn index(&self, index: V) -> &Self::Output {
+        self.get(index).expect("no value for the given id")
+    }
+}
+
+impl<E: Entity, T, V: ValidId<Entity = E>> std::ops::IndexMut<V> for SparseComponent<E, T> {
+    #[inline]
+    #[track_caller]
+    fn index_mut(&mut self, index: V) -> &mut Self::Output {
+        self.get_mut(index).expect("no value for the given id")
+    }
+}
+
+impl<'v, E: Entity, T> Valid<'v, SparseComponent<E, T>> {
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (Valid<'v, &Id<E>>, &T)> + '_ {
+        self.value.iter().map(|(k, v)| (Valid::new(k), v))
+    }
+
+    #[inline]
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Valid<'v, &Id<E>>, &mut T)> + '_ {
+        self.value.iter_mut().map(|(k, v)| (Valid::new(k), v))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{tests::Dyn, Allocator};
+
+    #[test]
+    fn insert_and_get() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut comp = SparseComponent::<Dyn, u32>::default();
+
+        let _ = a.create();
+        let id = a.create();
+
+        assert_eq!(None, comp.insert(id, 1));
+        assert_eq!(Some(1), comp.insert(id, 2));
+        assert_eq!(Some(&2), comp.get(id));
+        assert_eq!(1, comp.len());
+    }
+
+    #[test]
+    fn get_with_stale_id_returns_none() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut comp = SparseComponent::<Dyn, u32>::default();
+
+        let id = a.create().value;
+        a.kill(id);
+        let new = a.create();
+        comp.insert(new, 1);
+
+        assert!(!comp.contains(Valid::new(id)));
+        assert!(comp.contains(new));
+    }
+
+    #[test]
+    fn remove_moves_last_value() {
+        let mut a = Allocator::<Dyn>::default();
+        let a = a.create_only();
+        let mut comp = SparseComponent::<Dyn, u32>::default();
+
+        let id0 = a.create();
+        let id1 = a.create();
+        let id2 = a.create();
+        comp.insert(id0, 0);
+        comp.insert(id1, 1);
+        comp.insert(id2, 2);
+
+        assert_eq!(Some(0), comp.remove(id0));
+        assert_eq!(None, comp.remove(id0));
+
+        assert_eq!(&[2, 1], comp.values());
+        assert_eq!(2, comp[id2]);
+        assert_eq!(1, comp[id1]);
+    }
+
+    #[test]
+    fn insert_replaces_value_of_stale_id() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut comp = SparseComponent::<Dyn, u32>::default();
+
+        let id_a = a.create().value;
+        let id_c = a.create().value;
+        let stale = a.create().value;
+        comp.insert(Valid::new(id_a), 0);
+        comp.insert(Valid::new(id_c), 1);
+        comp.insert(Valid::new(stale), 2);
+
+        a.kill(stale);
+        let new = a.create();
+        assert_eq!(stale.index(), new.value.index());
+        assert_eq!(None, comp.insert(new, 3));
+
+        comp.remove(Valid::new(id_a));
+        comp.remove(Valid::new(id_c));
+
+        assert_eq!(Some(&3), comp.get(new));
+        assert_eq!(&[new.value], comp.ids());
+    }
+
+    #[test]
+    fn kill_many() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut comp = SparseComponent::<Dyn, u32>::default();
+
+        let id0 = a.create().value;
+        let id1 = a.create().value;
+        comp.insert(Valid::new(id0), 0);
+        comp.insert(Valid::new(id1), 1);
+
+        let killed = a.kill_many(&mut vec![id0]);
+        comp.kill_many(&killed);
+
+        let ids = comp
+            .validate(&a)
+            .iter()
+            .map(|(id, _)| *id.value)
+            .collect::<Vec<_>>();
+        assert_eq!(vec![id1], ids);
+    }
+
+    #[test]
+    #[should_panic]
+    fn validate_when_out_of_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut comp = SparseComponent::<Dyn, ()>::default();
+
+        let id = a.create();
+        comp.insert(id, ());
+
+        let id = id.value;
+        a.kill(id);
+
+        comp.validate(&a);
+    }
+
+    #[test]
+    fn validate_mut_when_in_sync() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut comp = SparseComponent::<Dyn, ()>::default();
+
+        let id = a.create();
+        comp.insert(id, ());
+
+        comp.kill(id);
+        let id = id.value;
+        a.kill(id);
+
+        comp.validate_mut(&a);
+    }
+
+    #[test]
+    fn remap() {
+        let mut a = Allocator::<Dyn>::default();
+        let mut comp = SparseComponent::<Dyn, u32>::default();
+
+        let ids = a.create_many(3).value;
+        comp.insert(Valid::new(ids[0]), 0);
+        comp.insert(Valid::new(ids[2]), 2);
+
+        let killed = a.kill_many(&mut vec![ids[0]]);
+        comp.kill_many(&killed);
+
+        let remap = a.compact();
+        comp.remap(&remap);
+
+        let new = remap.get(ids[2]);
+        assert_eq!(0, new.index());
+        assert_eq!(Some(&2), comp.validate(&a).value.get(Valid::new(new)));
+        assert!(!comp.contains(Valid::new(ids[2])));
+    }
+}
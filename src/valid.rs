@@ -1,13 +1,161 @@
+use crate::allocator::KilledIds;
 use crate::gen::AllocGen;
 use crate::id::Id;
-use crate::{Entity, Static, Dynamic};
+use crate::{DynamicIdType, Entity, Static};
 use iter_context::ContextualIterator;
 use ref_cast::RefCast;
 use std::marker::PhantomData;
 
-pub trait Validator<'v, E: Entity<IdType = Dynamic>>:
+pub trait Validator<'v, E: Entity<IdType: DynamicIdType>>:
     AsRef<AllocGen<E>>
 {
+    /// Compares the generation of a collection with that of the allocator
+    #[inline]
+    fn check(&self, gen: &AllocGen<E>) -> Result<(), SyncError<E>> {
+        if self.as_ref() == gen {
+            Ok(())
+        } else {
+            Err(SyncError::Diverged)
+        }
+    }
+}
+
+/// Describes how a collection is out of sync with its allocator.
+///
+/// Telling whether a collection is behind or ahead requires the allocator to keep a history,
+/// see [`crate::Allocator::with_history`]
+#[derive(Debug)]
+pub enum SyncError<E: Entity> {
+    /// The collection has missed the kills of these Ids
+    Behind { missed: Vec<Id<E>> },
+    /// The collection has seen the kills of these Ids, but the allocator has not,
+    /// such as when the allocator has been restored from a snapshot
+    Ahead { extra: Vec<Id<E>> },
+    /// The generation of the collection is not in the allocator's history
+    Diverged,
+}
+
+impl<E: Entity> Clone for SyncError<E> {
+    #[inline]
+    fn clone(&self) -> Self {
+        match self {
+            SyncError::Behind { missed } => SyncError::Behind {
+                missed: missed.clone(),
+            },
+            SyncError::Ahead { extra } => SyncError::Ahead {
+                extra: extra.clone(),
+            },
+            SyncError::Diverged => SyncError::Diverged,
+        }
+    }
+}
+
+impl<E: Entity> PartialEq for SyncError<E> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (SyncError::Behind { missed: l }, SyncError::Behind { missed: r }) => l == r,
+            (SyncError::Ahead { extra: l }, SyncError::Ahead { extra: r }) => l == r,
+            (SyncError::Diverged, SyncError::Diverged) => true,
+            _ => false,
+        }
+    }
+}
+
+impl<E: Entity> Eq for SyncError<E> {}
+
+impl<E: Entity> std::fmt::Display for SyncError<E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SyncError::Behind { missed } => write!(f, "behind by {} killed ids", missed.len()),
+            SyncError::Ahead { extra } => write!(f, "ahead by {} killed ids", extra.len()),
+            SyncError::Diverged => f.write_str("diverged from the allocator"),
+        }
+    }
+}
+
+impl<E: Entity> std::error::Error for SyncError<E> {}
+
+/// Unwraps the result of validating a collection,
+/// panicking with the name of the collection if it is out of sync
+#[inline]
+#[track_caller]
+pub(crate) fn expect_in_sync<C: ?Sized, T, E: Entity>(result: Result<T, SyncError<E>>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(error) => panic!(
+            "collection is out of sync with the allocator: {}: {error}",
+            std::any::type_name::<C>()
+        ),
+    }
+}
+
+/// Kills a batch of Ids from a collection,
+/// checking that the batch follows from the `AllocGen<E>` given by `gen`
+/// and that the collection counted every kill.
+/// With an order-independent checksum, batches can be applied in any order
+#[inline]
+#[track_caller]
+pub(crate) fn kill_each<C, E: Entity<IdType: DynamicIdType>, R>(
+    collection: &mut C,
+    gen: fn(&C) -> &AllocGen<E>,
+    killed: &KilledIds<E>,
+    mut kill: impl FnMut(&mut C, Id<E>) -> R,
+) {
+    let expected = gen(collection)
+        .after_kills(killed.before(), killed.after())
+        .expect("killed Ids do not follow from the kills of the collection");
+    for id in killed.ids() {
+        kill(collection, *id);
+    }
+    assert_eq!(gen(collection), &expected);
+}
+
+/// A collection that stays in sync with its allocator by tracking its kills in an `AllocGen<E>`.
+///
+/// Provides the validation and batch kills that are shared by every dynamic collection,
+/// which forward to these methods from their own.
+pub(crate) trait Synced<E: Entity<IdType: DynamicIdType>>: Sized {
+    fn alloc_gen(&self) -> &AllocGen<E>;
+
+    /// Kills a single Id, incrementing the `AllocGen<E>`
+    fn kill_id(&mut self, id: Id<E>);
+
+    #[inline]
+    #[track_caller]
+    fn kill_many(&mut self, killed: &KilledIds<E>) {
+        kill_each(self, Self::alloc_gen, killed, Self::kill_id);
+    }
+
+    #[inline]
+    #[track_caller]
+    fn validate<'v, V: Validator<'v, E>>(&self, v: V) -> &Valid<'v, Self> {
+        expect_in_sync::<Self, _, _>(Synced::try_validate(self, v))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn validate_mut<'v, V: Validator<'v, E>>(&mut self, v: V) -> &mut Valid<'v, Self> {
+        expect_in_sync::<Self, _, _>(Synced::try_validate_mut(self, v))
+    }
+
+    #[inline]
+    fn try_validate<'v, V: Validator<'v, E>>(
+        &self,
+        v: V,
+    ) -> Result<&Valid<'v, Self>, SyncError<E>> {
+        v.check(self.alloc_gen())?;
+        Ok(Valid::new_ref(self))
+    }
+
+    #[inline]
+    fn try_validate_mut<'v, V: Validator<'v, E>>(
+        &mut self,
+        v: V,
+    ) -> Result<&mut Valid<'v, Self>, SyncError<E>> {
+        v.check(self.alloc_gen())?;
+        Ok(Valid::new_mut(self))
+    }
 }
 
 pub trait ValidId: Copy {